@@ -0,0 +1,144 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Result, utils::cache_key};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct CacheConfig {
+    /// 是否启用页面和图片缓存
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cache_dir")]
+    pub dir: String,
+    /// 缓存有效期（秒），0 表示永不过期
+    #[serde(default)]
+    pub ttl: u64,
+    /// 仅使用缓存，不发起网络请求
+    #[serde(default)]
+    pub offline: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_cache_dir(),
+            ttl: 0,
+            offline: false,
+        }
+    }
+}
+
+fn default_cache_dir() -> String {
+    String::from("./cache")
+}
+
+/// 以url为键的页面和图片缓存
+pub struct PageCache {
+    dir: PathBuf,
+    ttl: Option<Duration>,
+    offline: bool,
+}
+
+impl PageCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Option<Duration>, offline: bool) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+            offline,
+        }
+    }
+
+    /// 未启用缓存时返回 `None`
+    pub fn from_config(config: &CacheConfig) -> Option<Self> {
+        if !config.enabled && !config.offline {
+            return None;
+        }
+        let ttl = (config.ttl > 0).then(|| Duration::from_secs(config.ttl));
+        Some(Self::new(&config.dir, ttl, config.offline))
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// 读取缓存，离线模式下忽略有效期
+    pub fn get(&self, url: &str) -> Option<String> {
+        String::from_utf8(self.read(self.path(url, "html"))?).ok()
+    }
+
+    pub fn put(&self, url: &str, html: &str) -> Result<()> {
+        self.write(self.path(url, "html"), html.as_bytes())
+    }
+
+    /// 读取缓存的图片，规则与页面相同
+    pub fn get_bytes(&self, url: &str) -> Option<Vec<u8>> {
+        self.read(self.path(url, "img"))
+    }
+
+    pub fn put_bytes(&self, url: &str, data: &[u8]) -> Result<()> {
+        self.write(self.path(url, "img"), data)
+    }
+
+    fn read(&self, path: PathBuf) -> Option<Vec<u8>> {
+        if !self.offline
+            && let Some(ttl) = self.ttl
+        {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+            if age > ttl {
+                return None;
+            }
+        }
+        fs::read(path).ok()
+    }
+
+    fn write(&self, path: PathBuf, data: &[u8]) -> Result<()> {
+        create_dir_all(&self.dir)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    fn path(&self, url: &str, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", cache_key(url), ext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_cache() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let cache = PageCache::new(dir, None, false);
+        let url = "https://www.bilinovel.com/novel/1/2.html";
+        assert!(cache.get(url).is_none());
+        cache.put(url, "<html></html>").unwrap();
+        assert_eq!(cache.get(url).as_deref(), Some("<html></html>"));
+
+        let expired = PageCache::new(dir, Some(Duration::ZERO), false);
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(expired.get(url).is_none());
+
+        let offline = PageCache::new(dir, Some(Duration::ZERO), true);
+        assert_eq!(offline.get(url).as_deref(), Some("<html></html>"));
+
+        // 图片与页面分开保存
+        let image = "https://img.example.com/1.jpg";
+        assert!(cache.get_bytes(image).is_none());
+        cache.put_bytes(image, b"jpg").unwrap();
+        assert_eq!(offline.get_bytes(image), Some(b"jpg".to_vec()));
+        assert!(cache.get_bytes(url).is_none());
+    }
+}
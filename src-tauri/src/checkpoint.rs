@@ -0,0 +1,125 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Result, model::Content, utils::cache_key};
+
+/// 已下载完成的一章
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChapterCheckpoint {
+    /// 章节标题，与目录不一致时视为过期
+    pub title: String,
+    pub contents: Vec<Content>,
+    pub images: Vec<String>,
+    /// 本章的url，作为插图的来源
+    pub url: String,
+    /// 下一章的url
+    pub next_url: String,
+}
+
+/// 下载中的卷的工作目录
+///
+/// 每下载完一章或一张插图就写入磁盘，重新下载同一卷时从断点继续，生成epub后删除。
+pub struct Checkpoint {
+    dir: PathBuf,
+}
+
+impl Checkpoint {
+    /// `volume`为卷链接，用于区分同一本书的不同卷
+    pub fn new(dir: impl AsRef<Path>, book_id: &str, volume: &str) -> Self {
+        Self {
+            dir: dir
+                .as_ref()
+                .join(cache_key(book_id))
+                .join(cache_key(volume)),
+        }
+    }
+
+    /// 读取第`index`章，没有断点或标题不一致时返回 `None`
+    pub fn load_chapter(&self, index: usize, title: &str) -> Option<ChapterCheckpoint> {
+        let json = fs::read_to_string(self.chapter_path(index)).ok()?;
+        serde_json::from_str::<ChapterCheckpoint>(&json)
+            .ok()
+            .filter(|chapter| chapter.title == title)
+    }
+
+    pub fn save_chapter(&self, index: usize, chapter: &ChapterCheckpoint) -> Result<()> {
+        create_dir_all(&self.dir)?;
+        fs::write(self.chapter_path(index), serde_json::to_string(chapter)?)?;
+        Ok(())
+    }
+
+    pub fn load_image(&self, url: &str) -> Option<Vec<u8>> {
+        fs::read(self.image_path(url))
+            .ok()
+            .filter(|data| !data.is_empty())
+    }
+
+    pub fn save_image(&self, url: &str, data: &[u8]) -> Result<()> {
+        create_dir_all(self.dir.join("images"))?;
+        fs::write(self.image_path(url), data)?;
+        Ok(())
+    }
+
+    /// 删除工作目录，书籍目录为空时一并删除
+    pub fn clear(&self) -> Result<()> {
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+        if let Some(parent) = self.dir.parent() {
+            let _ = fs::remove_dir(parent);
+        }
+        Ok(())
+    }
+
+    fn chapter_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("{:03}.json", index))
+    }
+
+    fn image_path(&self, url: &str) -> PathBuf {
+        self.dir.join("images").join(cache_key(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Inline;
+
+    #[test]
+    fn test_checkpoint() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let checkpoint = Checkpoint::new(dir, "1", "/novel/1/vol_2.html");
+
+        let chapter = ChapterCheckpoint {
+            title: String::from("第一章"),
+            contents: vec![
+                Content::Paragraph(vec![
+                    Inline::Text(String::from("正文")),
+                    Inline::Note(vec![Inline::Text(String::from("注释"))]),
+                ]),
+                Content::Image(String::from("https://img.example.com/1.jpg")),
+            ],
+            images: vec![String::from("https://img.example.com/1.jpg")],
+            url: String::from("https://www.bilinovel.com/novel/1/3.html"),
+            next_url: String::from("https://www.bilinovel.com/novel/1/4.html"),
+        };
+        assert!(checkpoint.load_chapter(0, "第一章").is_none());
+        checkpoint.save_chapter(0, &chapter).unwrap();
+        assert_eq!(checkpoint.load_chapter(0, "第一章"), Some(chapter));
+        // 目录变化后不再使用
+        assert!(checkpoint.load_chapter(0, "序章").is_none());
+
+        let url = "https://img.example.com/1.jpg";
+        checkpoint.save_image(url, b"png").unwrap();
+        assert_eq!(checkpoint.load_image(url), Some(b"png".to_vec()));
+
+        checkpoint.clear().unwrap();
+        assert!(checkpoint.load_image(url).is_none());
+        assert!(!dir.join(cache_key("1")).exists());
+    }
+}
@@ -1,18 +1,46 @@
 use std::process;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 use crate::{
-    config::Config,
+    bail,
+    config::{CONFIG_FILE, Config},
+    cookie_jar::CookieJar,
     downloader::{Downloader, DownloaderConfig},
     error::Result,
+    model::{BookInfo, BookListKind, BookSummary},
+    progress::{self, ProgressStyle},
+    queue::{DownloadQueue, QueueMode, QueueStatus, download_missing, run_queue},
+    recorder::{HttpMode, RecorderConfig},
+    site_profile::SiteProfile,
+    source::{BiliSource, NovelSource},
+    utils::parse_volume_list,
 };
 
 #[derive(Parser, Debug)]
-#[command(version = "0.1", about, long_about = None)]
+#[command(
+    version = "0.1",
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true,
+    after_help = "退出码：
+  0  成功
+  1  其他错误
+  10 该书内容已删除
+  11 内容审核未通过
+  12 小说不存在
+  13 触发反爬，访问被限制
+  14 url重定向
+  15 网络错误
+  16 页面解析失败
+  17 离线模式下缓存中没有需要的页面或图片"
+)]
 struct Args {
-    #[arg(short, long, help = "书籍id")]
-    book_id: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, required = true, help = "书籍id")]
+    book_id: Option<String>,
 
     #[arg(short, long, default_value_t = String::new(), help = "需要下载的卷数，下载多卷请使用,分隔或者连字符-，下载所有使用all")]
     volume: String,
@@ -23,6 +51,16 @@ struct Args {
     #[arg(long)]
     cookie: Option<String>,
 
+    #[arg(
+        long,
+        global = true,
+        help = "配置文件，默认为 ./config.json，命令行参数会覆盖其中的设置"
+    )]
+    config: Option<String>,
+
+    #[arg(long, global = true, help = "保存cookie的文件，默认为 ./cookies.json")]
+    cookie_file: Option<String>,
+
     #[arg(
         short,
         long,
@@ -37,14 +75,299 @@ struct Args {
     )]
     template: Option<String>,
 
+    #[arg(long, help = "更新已下载的卷，只下载新增或变化的章节")]
+    update: bool,
+
+    #[arg(long, help = "把选中的卷合并为一个epub，目录分为卷和章节两级")]
+    omnibus: bool,
+
     #[arg(long, help = "开启调试模式，输出更多日志")]
     debug: Option<bool>,
+
+    #[arg(long, help = "同时下载的插图数量，默认为4")]
+    image_concurrency: Option<usize>,
+
+    #[arg(long, help = "页面和图片的缓存目录，设置后启用缓存")]
+    cache_dir: Option<String>,
+
+    #[arg(long, help = "页面缓存有效期（秒），0 表示永不过期")]
+    cache_ttl: Option<u64>,
+
+    #[arg(long, help = "离线模式，只使用缓存中的页面和图片")]
+    offline: bool,
+
+    #[arg(long, conflicts_with = "replay", help = "把所有请求和响应录制到该目录")]
+    record: Option<String>,
+
+    #[arg(long, help = "从该目录回放录制的请求，不联网")]
+    replay: Option<String>,
+
+    #[arg(
+        long,
+        help = "代理地址，支持 http、https 和 socks5，例如 socks5://127.0.0.1:1080"
+    )]
+    proxy: Option<String>,
+
+    #[arg(long, help = "代理用户名")]
+    proxy_user: Option<String>,
+
+    #[arg(long, help = "代理密码")]
+    proxy_password: Option<String>,
+
+    #[arg(long, value_delimiter = ',', help = "不使用代理的域名，多个使用,分隔")]
+    no_proxy: Option<Vec<String>>,
+
+    #[arg(long, help = "图片请求使用的代理地址，默认与页面相同")]
+    image_proxy: Option<String>,
+
+    #[arg(long, help = "只列出每章中会被过滤规则删除的段落，不删除")]
+    filter_dry_run: bool,
+
+    #[arg(long, global = true, help = "站点配置文件，用于修改页面解析规则")]
+    site_profile: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = ProgressStyle::Bar,
+        help = "下载进度的输出格式，json 时每个事件输出一行，日志输出到标准错误"
+    )]
+    progress: ProgressStyle,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 管理保存的cookie
+    Cookies {
+        #[command(subcommand)]
+        action: CookieAction,
+    },
+    /// 按关键词搜索书籍
+    Search { keyword: String },
+    /// 输出当前使用的站点配置，保存后可以修改解析规则
+    Profile,
+    /// 列出作者的所有作品
+    Author {
+        /// 作者名或作者页链接
+        author: String,
+        #[arg(long, help = "下载所有作品中书库里还没有的卷")]
+        download: bool,
+    },
+    /// 管理下载队列
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+    /// 浏览排行榜和分类列表
+    Browse {
+        #[arg(value_enum)]
+        kind: BookListKind,
+        #[arg(long, default_value_t = 1, help = "页码")]
+        page: u32,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CookieAction {
+    /// 以 cookies.txt 格式显示保存的cookie
+    Show,
+    /// 清空保存的cookie
+    Clear,
+    /// 从浏览器导出的 cookies.txt 或json文件导入cookie
+    Import { file: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum QueueAction {
+    /// 添加下载任务
+    Add {
+        /// 书籍id
+        book_id: String,
+        #[arg(
+            short,
+            long,
+            default_value = "all",
+            help = "需要下载的卷数，格式与 --volume 相同"
+        )]
+        volume: String,
+        #[arg(long, conflicts_with = "omnibus", help = "只下载已有文件中没有的章节")]
+        update: bool,
+        #[arg(long, help = "把选中的卷合并为一个epub")]
+        omnibus: bool,
+    },
+    /// 列出队列中的任务
+    List,
+    /// 删除任务
+    Remove { id: u32 },
+    /// 依次下载队列中的任务
+    Run,
+}
+
+fn queue_status(status: QueueStatus) -> &'static str {
+    match status {
+        QueueStatus::Pending => "等待",
+        QueueStatus::Running => "下载中",
+        QueueStatus::Done => "完成",
+        QueueStatus::Failed => "失败",
+    }
+}
+
+async fn run_queue_action(config: &Config, action: QueueAction) -> Result<()> {
+    let mut queue = DownloadQueue::load(&config.queue.file)?;
+    match action {
+        QueueAction::Add {
+            book_id,
+            volume,
+            update,
+            omnibus,
+        } => {
+            let mode = if omnibus {
+                QueueMode::Omnibus
+            } else if update {
+                QueueMode::Update
+            } else {
+                QueueMode::Download
+            };
+            let entry = queue.add(book_id, None, volume, mode)?;
+            println!("已添加任务{}", entry.id);
+        }
+        QueueAction::List => {
+            println!("队列文件: {}", config.queue.file);
+            for entry in queue.entries() {
+                println!(
+                    "[{}] {} {} 第{}卷 {:?} {}",
+                    entry.id,
+                    entry.book_id,
+                    entry.title.as_deref().unwrap_or_default(),
+                    entry.volumes,
+                    entry.mode,
+                    queue_status(entry.status),
+                );
+                if let Some(error) = &entry.error {
+                    println!("    {}", error);
+                }
+            }
+        }
+        QueueAction::Remove { id } => {
+            if !queue.remove(id)? {
+                bail!("任务{}不存在", id);
+            }
+            println!("已删除任务{}", id);
+        }
+        QueueAction::Run => {
+            let failed = run_queue(config, None).await?;
+            if failed > 0 {
+                bail!("{}个任务下载失败", failed);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_cookies(action: CookieAction, cookie_file: &str) -> Result<()> {
+    let jar = CookieJar::default();
+    jar.load_file(cookie_file)?;
+    match action {
+        CookieAction::Show => println!("{}", jar.to_netscape()),
+        CookieAction::Clear => {
+            jar.clear()?;
+            println!("已清空cookie");
+        }
+        CookieAction::Import { file } => {
+            let count = jar.import(file)?;
+            println!("已导入{}个cookie到{}", count, cookie_file);
+        }
+    }
+    Ok(())
+}
+
+fn print_books(books: Vec<BookSummary>) {
+    for book in books {
+        let mut info = vec![book.author.unwrap_or_default()];
+        info.extend(book.status);
+        info.extend(book.tags);
+        println!("[{}] {}  {}", book.id, book.title, info.join(" / "));
+    }
+}
+
+async fn run_search(config: &Config, keyword: &str) -> Result<()> {
+    let source = BiliSource::from_config(config)?;
+    let result = source.search(keyword, None).await?;
+    if result.is_empty() {
+        println!("没有找到与“{}”相关的书籍", keyword);
+        return Ok(());
+    }
+    print_books(result);
+    Ok(())
+}
+
+async fn run_browse(config: &Config, kind: BookListKind, page: u32) -> Result<()> {
+    let source = BiliSource::from_config(config)?;
+    let list = source.fetch_book_list(kind, page, None).await?;
+    print_books(list.books);
+    println!("第{}/{}页", list.page, list.total_pages);
+    Ok(())
+}
+
+async fn run_author(config: &Config, author: &str, download: bool) -> Result<()> {
+    let source = BiliSource::from_config(config)?;
+    let books = source.fetch_author_books(author, None).await?;
+    if books.is_empty() {
+        println!("没有找到“{}”的作品", author);
+        return Ok(());
+    }
+    print_books(books.clone());
+    if !download {
+        return Ok(());
+    }
+
+    download_missing(config, books, None).await
+}
+
+fn print_book_info(info: &BookInfo) {
+    println!("{}", info.title.as_deref().unwrap_or_default());
+    if !info.aliases.is_empty() {
+        println!("别名: {}", info.aliases.join(" / "));
+    }
+    let fields = [
+        ("作者", info.author.clone()),
+        ("插画", info.illustrator.clone()),
+        ("状态", info.status.clone()),
+        ("字数", info.word_count.map(|count| count.to_string())),
+        ("最后更新", info.last_update.clone()),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            println!("{}: {}", name, value);
+        }
+    }
 }
 
 pub async fn run_cli() -> Result<()> {
-    let args = Args::parse();
+    run(Args::parse()).await
+}
+
+async fn run(args: Args) -> Result<()> {
+    progress::set_style(args.progress);
+
+    // 与图形界面使用同一份配置，过滤规则、队列文件等都从配置文件读取
+    let mut config = Config::load_file(args.config.as_deref().unwrap_or(CONFIG_FILE));
+    if let Some(cookie_file) = args.cookie_file {
+        config.cookie_file = cookie_file;
+    }
+    if let Some(site_profile) = args.site_profile {
+        config.site_profile = site_profile;
+    }
+    let command = match args.command {
+        Some(Command::Cookies { action }) => return run_cookies(action, &config.cookie_file),
+        Some(Command::Profile) => {
+            println!("{}", SiteProfile::load(&config.site_profile)?.to_json()?);
+            return Ok(());
+        }
+        command => command,
+    };
 
-    let mut config = Config::default();
     if let Some(output) = args.output {
         config.output = output;
     }
@@ -57,48 +380,165 @@ pub async fn run_cli() -> Result<()> {
     if let Some(debug) = args.debug {
         config.debug = debug;
     }
+    if let Some(image_concurrency) = args.image_concurrency {
+        config.image_concurrency = image_concurrency;
+    }
+    if let Some(cache_dir) = args.cache_dir {
+        config.cache.enabled = true;
+        config.cache.dir = cache_dir;
+    }
+    if let Some(cache_ttl) = args.cache_ttl {
+        config.cache.ttl = cache_ttl;
+    }
+    if args.offline {
+        config.cache.offline = true;
+    }
+    if args.filter_dry_run {
+        config.filter.dry_run = true;
+    }
+    if let Some(dir) = args.record {
+        config.recorder = RecorderConfig {
+            mode: HttpMode::Record,
+            dir,
+        };
+    }
+    if let Some(proxy) = args.proxy {
+        config.proxy.url = proxy;
+    }
+    if let Some(username) = args.proxy_user {
+        config.proxy.username = username;
+    }
+    if let Some(password) = args.proxy_password {
+        config.proxy.password = password;
+    }
+    if let Some(no_proxy) = args.no_proxy {
+        config.proxy.no_proxy = no_proxy;
+    }
+    if let Some(image_proxy) = args.image_proxy {
+        config.proxy.image_url = image_proxy;
+    }
+    if let Some(dir) = args.replay {
+        config.recorder = RecorderConfig {
+            mode: HttpMode::Replay,
+            dir,
+        };
+    }
+    match command {
+        Some(Command::Search { keyword }) => return run_search(&config, &keyword).await,
+        Some(Command::Browse { kind, page }) => return run_browse(&config, kind, page).await,
+        Some(Command::Author { author, download }) => {
+            return run_author(&config, &author, download).await;
+        }
+        Some(Command::Queue { action }) => return run_queue_action(&config, action).await,
+        _ => (),
+    }
 
-    let book = Downloader::new(DownloaderConfig {
-        base_url: config.base_url.clone(),
-        book_id: args.book_id,
-        output: config.output.clone(),
-        template: config.template.clone(),
-        sleep_time: config.sleep_time,
-        convert_simple_chinese: config.convert_simple_chinese,
-        cookie: config.cookie.clone(),
-        user_agent: config.user_agent.clone(),
-        header_map: config.headers.clone(),
-        add_catalog: config.add_catalog,
-        error_img: config.error_img.clone(),
-        app_handle: None,
-        debug: config.debug,
-    })
+    let book = Downloader::new(
+        DownloaderConfig::from_config(&config, args.book_id.unwrap_or_default(), None)?,
+        BiliSource::from_config(&config)?,
+    )
     .await?;
 
     if args.volume.is_empty() {
-        println!("{}", book.book_info.title.unwrap());
+        print_book_info(&book.book_info);
         for i in 0..book.volume_infos.len() {
             println!("[{}] {:?}", i + 1, book.volume_infos[i].title);
         }
         process::exit(0);
     }
 
-    if args.volume == "all" {
-        book.download(1..=book.volume_infos.len().try_into().unwrap())
-            .await?;
-    } else if args.volume.contains(',') {
-        let list: std::str::Split<'_, &str> = args.volume.split(",");
-        let list = list.map(|s| s.parse::<u32>().unwrap());
-        book.download(list).await?;
-    } else if args.volume.contains('-') {
-        let list = args.volume.split("-");
-        let mut list = list.map(|s| s.parse::<u32>().unwrap());
-        let start = list.next().unwrap();
-        let end = list.next().unwrap();
-        book.download(start..=end).await?;
+    let list = parse_volume_list(&args.volume, book.volume_infos.len())?;
+    if args.omnibus {
+        book.download_omnibus(list.into_iter()).await?;
+    } else if args.update {
+        book.update(list.into_iter()).await?;
     } else {
-        book.download(vec![args.volume.parse::<u32>().unwrap()].into_iter())
-            .await?;
+        book.download(list.into_iter()).await?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read};
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::runtime::{RUN_MODE, RunMode};
+
+    #[tokio::test]
+    async fn test_run_cli_with_config_file() {
+        *RUN_MODE.lock() = RunMode::Cli;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let config = dir.join("config.json");
+        std::fs::write(
+            &config,
+            serde_json::json!({
+                "checkpointDir": dir.join("checkpoint"),
+                "filter": {"rules": [{"pattern": "第一章的第一段"}]},
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let replay = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/replay");
+        let args = Args::try_parse_from([
+            "epub_download",
+            "--config",
+            config.to_str().unwrap(),
+            "--replay",
+            replay,
+            "-o",
+            dir.to_str().unwrap(),
+            "-b",
+            "9999",
+            "-v",
+            "1",
+        ])
+        .unwrap();
+        run(args).await.unwrap();
+
+        let path = dir.join("测试小说").join("测试小说-第一卷.epub");
+        let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        let mut chapter = String::new();
+        zip.by_name("OEBPS/Text/001.xhtml")
+            .unwrap()
+            .read_to_string(&mut chapter)
+            .unwrap();
+        // 配置文件中的规则生效
+        assert!(!chapter.contains("第一章的第一段"));
+        assert!(chapter.contains("第一章分页的内容。"));
+    }
+
+    #[tokio::test]
+    async fn test_queue_uses_config_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let config = dir.join("config.json");
+        let queue_file = dir.join("gui_queue.json");
+        std::fs::write(
+            &config,
+            serde_json::json!({"queue": {"file": queue_file}}).to_string(),
+        )
+        .unwrap();
+
+        let args = Args::try_parse_from([
+            "epub_download",
+            "--config",
+            config.to_str().unwrap(),
+            "queue",
+            "add",
+            "9999",
+            "--update",
+        ])
+        .unwrap();
+        run(args).await.unwrap();
+
+        // 与图形界面管理同一个队列文件
+        let queue = DownloadQueue::load(&queue_file).unwrap();
+        assert_eq!(queue.entries().len(), 1);
+        assert_eq!(queue.entries()[0].mode, QueueMode::Update);
+    }
+}
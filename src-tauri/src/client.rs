@@ -1,22 +1,32 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path, sync::Arc};
 
+use parking_lot::RwLock;
 use reqwest::{
-    Client,
-    header::{ACCEPT, ACCEPT_LANGUAGE, COOKIE, HeaderMap, HeaderName, HeaderValue, USER_AGENT},
+    Client, Proxy, RequestBuilder,
+    header::{ACCEPT, ACCEPT_LANGUAGE, HeaderMap, HeaderName, HeaderValue, USER_AGENT},
 };
 use url::Url;
 
 use crate::{
-    bail, err,
-    error::Result,
+    bail,
+    cache::PageCache,
+    cookie_jar::CookieJar,
+    err,
+    error::{Result, SiteError},
     message::send,
-    model::App,
+    model::{App, BookListKind},
+    progress::{DownloadEvent, emit},
+    proxy::ProxyConfig,
+    rate_limit::{RateLimitConfig, RateLimiter, RequestKind},
+    recorder::{HttpMode, HttpRecorder},
+    redirect::{RedirectConfig, moved_origin},
+    retry::{FailureClass, Retrier, RetryConfig},
+    site_profile::{Markers, SiteProfile},
     utils::{self, t2s},
 };
 
 pub fn get_headers(
     referer: &str,
-    mut cookie: &str,
     mut user_agent: &str,
     header_map: &HashMap<String, String>,
 ) -> Result<HeaderMap> {
@@ -36,9 +46,6 @@ pub fn get_headers(
         return Ok(headers);
     }
 
-    if cookie.is_empty() {
-        cookie = "cf_clearance=nnBCOk2fKYYFXQQHuSW1aC1KqJ1rm3vt0Ixi6jzs_uc-1777606156-1.2.1.1-zgZcotszQJF_JWRQUeiOahUkcrJPiKQdy5ZtMNnv_hc_drIMUbeU26vN0.9kQ.ebhJYpFYsANmQ.u9BM490dq_23ymgNYnWVb2jxzpl4.tq8UzYWzu51i4sRAljH4l4AUvTsuJcMcfkBA9.I2C5JvYRYa5JKAemzN___CvqC0cxa9hXuorc2VHlO2yMP9jF6hAF3FQ0UecX9966SFjwYynX72olsk.ID6O3JEIEgnr7WW9sRd.BIJ3YLKkLljMpTJM36XvoNI_ZoRLec.2dLfe71D2r2NEiOXx_M4v7QAUqhsAfNKKQ75rALNPBipqctzfYgaItenPxhssp_p_10lg; jieqiVisitInfo=jieqiUserLogin%3D1778069650%2CjieqiUserId%3D220564; jieqiUserInfo=jieqiUserId%3D220564%2CjieqiUserUname%3Dkomaeda%2CjieqiUserName%3Dkomaeda%2CjieqiUserGroup%3D3%2CjieqiUserGroupName%3D%E6%99%AE%E9%80%9A%E4%BC%9A%E5%91%98%2CjieqiUserVip%3D0%2CjieqiUserHonorId%3D5%2CjieqiUserHonor%3D%E6%AF%92%E8%88%8C%2CjieqiUserToken%3D60e9be1ac557b7ade41d777be68f968c%2CjieqiCodeLogin%3D0%2CjieqiCodePost%3D0%2CjieqiUserPassword%3Dbe65b6881aa08fee8f24550b3dc712b5%2CjieqiUserLogin%3D1778069650; _ga_1K4JZ603WH=GS2.1.s1778073948$o20$g0$t1778073948$j60$l0$h0; _ga=GA1.1.518296446.1772889511; PHPSESSID=ug7bd7c2n18hn5lpa0fsun4qdk; night=1; jieqiRecentRead=2773.132372.0.1.1765563307.220564-4644.273875.0.1.1769339259.0-5027.311281.0.1.1771072425.0-1.108523.0.1.1771084732.0-3193.162550.0.1.1774450138.220564-3382.305740.0.1.1774799485.220564-2979.309218.0.1.1777606176.220564-2342.123395.0.1.1778069676.220564"
-    }
     if user_agent.is_empty() {
         user_agent = "Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/137.0.0.0 Safari/537.36";
     }
@@ -50,7 +57,6 @@ pub fn get_headers(
         ),
     );
     headers.insert(ACCEPT, HeaderValue::from_static(r"text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7"));
-    headers.insert(COOKIE, HeaderValue::from_str(cookie.trim())?);
     headers.insert(
         "Referer",
         HeaderValue::from_str(&(referer.to_string() + "/novel/4353/250879.html"))?,
@@ -84,11 +90,37 @@ pub fn get_headers(
     Ok(headers)
 }
 
+/// 一次请求的响应
+pub struct HttpResponse {
+    /// 重定向后的最终url
+    pub url: String,
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
 pub struct BiliClient {
     client: Client,
+    /// 图片请求使用的 client，可以配置单独的代理
+    image_client: Client,
+    headers: HeaderMap,
+    cookie_jar: Arc<CookieJar>,
     base_url: Url,
     convert_simple_chinese: bool,
     debug: bool,
+    cache: Option<PageCache>,
+    recorder: Option<HttpRecorder>,
+    retry: RetryConfig,
+    limiter: RateLimiter,
+    redirect: RedirectConfig,
+    profile: Arc<SiteProfile>,
+    /// 已经迁移的站点地址，旧地址 -> 新地址
+    moved: RwLock<HashMap<String, String>>,
 }
 
 impl BiliClient {
@@ -100,99 +132,288 @@ impl BiliClient {
         convert_simple_chinese: bool,
         debug: bool,
     ) -> Result<Self> {
-        let headers = get_headers(referer, cookie, user_agent, header_map)?;
+        let headers = get_headers(referer, user_agent, header_map)?;
+        let base_url = Url::parse(referer)?;
+        let cookie_jar = Arc::new(CookieJar::default());
+        cookie_jar.add_cookie_str(cookie, &base_url);
+        let client = Client::builder()
+            .default_headers(headers.clone())
+            .cookie_provider(cookie_jar.clone())
+            .build()?;
         Ok(Self {
-            client: Client::builder().default_headers(headers).build()?,
-            base_url: Url::parse(referer)?,
+            image_client: client.clone(),
+            client,
+            headers,
+            cookie_jar,
+            base_url,
             convert_simple_chinese,
             debug,
+            cache: None,
+            recorder: None,
+            retry: RetryConfig::default(),
+            limiter: RateLimiter::new(RateLimitConfig::default()),
+            redirect: RedirectConfig::default(),
+            profile: Arc::new(SiteProfile::default()),
+            moved: RwLock::new(HashMap::new()),
         })
     }
 
+    /// 设置代理，页面、图片和更新检测请求都会使用
+    pub fn with_proxy(mut self, proxy: &ProxyConfig) -> Result<Self> {
+        self.client = self.build_client(proxy.page_proxy()?)?;
+        self.image_client = self.build_client(proxy.image_proxy()?)?;
+        Ok(self)
+    }
+
+    fn build_client(&self, proxy: Option<Proxy>) -> Result<Client> {
+        let mut builder = Client::builder()
+            .default_headers(self.headers.clone())
+            .cookie_provider(self.cookie_jar.clone());
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// 从文件读取cookie，服务器返回的cookie会保存到该文件，路径为空时不保存
+    pub fn with_cookie_file(self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.as_os_str().is_empty() {
+            self.cookie_jar.load_file(path)?;
+        }
+        Ok(self)
+    }
+
+    /// 设置页面缓存，`get_html` 会优先读取缓存
+    pub fn with_cache(mut self, cache: Option<PageCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// 设置请求录制或回放
+    pub fn with_recorder(mut self, recorder: Option<HttpRecorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// 设置按域名限速，同一个 client 发出的请求共享限速器
+    pub fn with_rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.limiter = RateLimiter::new(rate_limit);
+        self
+    }
+
+    /// 设置允许跟随的重定向
+    pub fn with_redirect(mut self, redirect: RedirectConfig) -> Self {
+        self.redirect = redirect;
+        self
+    }
+
+    /// 设置站点配置，页面状态判断和解析都会使用
+    pub fn with_profile(mut self, profile: SiteProfile) -> Self {
+        self.profile = Arc::new(profile);
+        self
+    }
+
+    pub fn profile(&self) -> &SiteProfile {
+        &self.profile
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+
+    fn is_replay(&self) -> bool {
+        self.recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.mode() == HttpMode::Replay)
+    }
+
+    /// 所有请求都经过这里，以便录制和回放
+    async fn send(
+        &self,
+        request: RequestBuilder,
+        url: &str,
+        kind: RequestKind,
+    ) -> Result<HttpResponse> {
+        if let Some(recorder) = &self.recorder
+            && recorder.mode() == HttpMode::Replay
+        {
+            return recorder.load(url);
+        }
+
+        self.limiter.acquire(url, kind).await;
+        let res = request.send().await?;
+        let response = HttpResponse {
+            url: res.url().to_string(),
+            status: res.status().as_u16(),
+            body: res.bytes().await?.to_vec(),
+        };
+
+        if let Some(recorder) = &self.recorder
+            && recorder.mode() == HttpMode::Record
+        {
+            recorder.save(url, &response)?;
+        }
+        Ok(response)
+    }
+
     pub async fn get(&self, url: &str) -> Result<String> {
-        if let Ok(res) = self.client.get(url).send().await {
-            Ok(res.text().await?)
+        if let Ok(res) = self
+            .send(self.client.get(url), url, RequestKind::Page)
+            .await
+        {
+            Ok(res.text())
         } else {
             bail!("请求失败")
         }
     }
 
-    pub async fn get_html(
-        &self,
-        url: &str,
-        message: Option<&App>,
-        sleep_time: u32,
-    ) -> Result<String> {
-        println!("  {url}");
+    /// 把已迁移站点的url改写到新地址
+    fn rewrite_url(&self, url: &str) -> String {
+        let moved = self.moved.read();
+        for (from, to) in moved.iter() {
+            if let Some(rest) = url.strip_prefix(from.as_str()) {
+                return format!("{}{}", to, rest);
+            }
+        }
+        url.to_string()
+    }
+
+    /// 检查重定向是否允许跟随，只是换了协议或域名时记录新地址
+    fn check_redirect(&self, from: &str, to: &str, message: Option<&App>) -> Result<()> {
+        let (from_url, to_url) = (Url::parse(from)?, Url::parse(to)?);
+        if !self.redirect.allows(&from_url, &to_url) {
+            send(message, "url重定向");
+            send(message, &format!("原始url: {}", from));
+            send(message, &format!("重定向到: {}", to));
+            bail!(SiteError::Redirected {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+        send(message, &format!("url重定向到: {}", to));
+        if let Some((old, new)) = moved_origin(&from_url, &to_url) {
+            let mut moved = self.moved.write();
+            for target in moved.values_mut() {
+                if *target == old {
+                    *target = new.clone();
+                }
+            }
+            moved.insert(old, new);
+        }
+        Ok(())
+    }
 
-        tokio::time::sleep(std::time::Duration::from_secs(sleep_time.into())).await;
+    fn page_fetched(&self, url: &str, message: Option<&App>) {
+        emit(
+            message,
+            DownloadEvent::PageFetched {
+                url: url.to_string(),
+            },
+        );
+    }
+
+    pub async fn get_html(&self, url: &str, message: Option<&App>) -> Result<String> {
+        if let Some(cache) = &self.cache {
+            if let Some(text) = cache.get(url) {
+                self.page_fetched(url, message);
+                return Ok(self.convert_text(text));
+            }
+            if cache.is_offline() {
+                bail!(SiteError::CacheMiss {
+                    url: url.to_string()
+                });
+            }
+        }
 
+        let request_url = self.rewrite_url(url);
+        let mut retrier = Retrier::new(&self.retry);
         loop {
-            if let Ok(res) = self.client.get(url).send().await {
-                if res.url().as_str() != url {
-                    send(message, "url重定向");
-                    send(message, &format!("原始url: {}", url));
-                    send(message, &format!("重定向到: {}", res.url()));
-                    bail!("url重定向");
-                }
-                if let Ok(t) = res.text().await {
-                    let mut text = t;
-                    if self.convert_simple_chinese {
-                        text = t2s(&text);
+            match self
+                .send(
+                    self.client.get(&request_url),
+                    &request_url,
+                    RequestKind::Page,
+                )
+                .await
+            {
+                Ok(res) => {
+                    if res.url != request_url {
+                        self.check_redirect(&request_url, &res.url, message)?;
                     }
-                    if text.contains("used Cloudflare to restrict access") {
-                        send(message, "下载频繁，触发反爬，正在重试....");
-                        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                    let raw = res.text();
+                    let text = self.convert_text(raw.clone());
+                    let markers = &self.profile.markers;
+                    if Markers::matches(&markers.anti_bot, &text) {
+                        self.limiter.slow_down(&request_url, RequestKind::Page);
+                        retrier
+                            .wait(FailureClass::AntiBot, "下载频繁，触发反爬", message)
+                            .await?;
                         continue; // 重试
                     }
-                    if text.contains("Just a moment...") || text.contains("403 Forbidden") {
+                    if Markers::matches(&markers.blocked, &text) {
                         send(message, "下载失败，请稍后再试");
-                        bail!("下载失败，请稍后再试");
+                        bail!(SiteError::blocked("下载失败，请稍后再试"));
                     }
-                    if text.contains("對不起，該書內容已刪除")
-                        || text.contains("对不起，该书内容已删除")
-                    {
+                    if Markers::matches(&markers.deleted, &text) {
                         send(message, "该书内容已删除");
-                        bail!("该书内容已删除");
+                        bail!(SiteError::Deleted);
                     }
-                    if text.contains("章節內容審核未通過") || text.contains("章节内容审核未通过")
-                    {
+                    if Markers::matches(&markers.not_reviewed, &text) {
                         send(message, "该书内容审核未通过");
-                        bail!("该书内容审核未通过");
-                    }
-                    if text.contains("抱歉，该小说未经审核")
-                        || text.contains("抱歉，該小說未經審核")
-                    {
-                        send(message, "该小说未经审核");
-                        bail!("该小说未经审核");
+                        bail!(SiteError::NotReviewed);
                     }
-                    if text.contains("抱歉，該小說不存在") || text.contains("抱歉，该小说不存在")
-                    {
+                    if Markers::matches(&markers.not_found, &text) {
                         send(message, "该小说不存在");
-                        bail!("该小说不存在");
+                        bail!(SiteError::NotFound);
                     }
-                    if text.contains("通告～客戶端停用中")
-                        || text.contains("通告～客户端停用中")
-                        || text.contains("內容加载失败")
-                        || text.contains("手机版页面由于相容性问题暂不支持电脑端阅读")
-                    {
-                        send(message, "无法下载完整内容，正在重试....");
-                        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                    if Markers::matches(&markers.unavailable, &text) {
+                        retrier
+                            .wait(FailureClass::Unavailable, "无法下载完整内容", message)
+                            .await?;
                         continue; // 重试
                     }
+                    self.limiter.record_success(&request_url, RequestKind::Page);
+                    if let Some(cache) = &self.cache
+                        && let Err(err) = cache.put(url, &raw)
+                    {
+                        send(message, &format!("写入缓存失败: {:?}", err));
+                    }
+                    self.page_fetched(url, message);
                     return Ok(text);
                 }
+                // 回放模式下缺少记录时重试没有意义
+                Err(err) if self.is_replay() => return Err(err),
+                Err(err) => {
+                    retrier
+                        .wait(
+                            FailureClass::Network,
+                            &format!("请求失败: {}", err),
+                            message,
+                        )
+                        .await?;
+                }
             }
-            send(message, "请求失败，正在重试....");
-            tokio::time::sleep(std::time::Duration::from_secs(3)).await;
-            // 继续循环重试
+        }
+    }
+
+    fn convert_text(&self, text: String) -> String {
+        if self.convert_simple_chinese {
+            t2s(&text)
+        } else {
+            text
         }
     }
 
     pub async fn get_novel(&self, book_id: &str, message: Option<&App>) -> Result<String> {
         let url = self.base_url.join(&format!("/novel/{}.html", book_id))?;
 
-        self.get_html(url.as_str(), message, 0).await
+        self.get_html(url.as_str(), message).await
     }
 
     pub async fn get_volume(
@@ -205,17 +426,70 @@ impl BiliClient {
             .base_url
             .join(&format!("/novel/{}/vol_{}.html", book_id, volume_id))?;
 
-        self.get_html(url.as_str(), message, 0).await
+        self.get_html(url.as_str(), message).await
+    }
+
+    pub async fn search(&self, keyword: &str, message: Option<&App>) -> Result<String> {
+        let mut url = self.base_url.join("/search.html")?;
+        url.query_pairs_mut().append_pair("searchkey", keyword);
+
+        self.get_html(url.as_str(), message).await
+    }
+
+    pub async fn get_book_list(
+        &self,
+        kind: BookListKind,
+        page: u32,
+        message: Option<&App>,
+    ) -> Result<String> {
+        let path = match kind {
+            BookListKind::LastUpdate => format!("/top/lastupdate/{}.html", page),
+            BookListKind::Newest => format!("/top/postdate/{}.html", page),
+            BookListKind::Popular => format!("/top/allvisit/{}.html", page),
+            BookListKind::MonthVisit => format!("/top/monthvisit/{}.html", page),
+            BookListKind::Favorite => format!("/top/goodnum/{}.html", page),
+            BookListKind::Finished => format!("/wenku/lastupdate_0_0_0_1_0_0_{}_0.html", page),
+        };
+        let url = self.base_url.join(&path)?;
+
+        self.get_html(url.as_str(), message).await
+    }
+
+    /// 作者页，`author`为作者名或作者页链接
+    pub async fn get_author(&self, author: &str, message: Option<&App>) -> Result<String> {
+        if author.starts_with("http") {
+            return self.get_html(author, message).await;
+        }
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| err!("无效的站点地址: {}", self.base_url))?
+            .clear()
+            .push("authorarticle")
+            .push(&format!("{}.html", author.trim()));
+
+        self.get_html(url.as_str(), message).await
     }
 
     pub async fn get_catalog(&self, book_id: &str, message: Option<&App>) -> Result<String> {
         let url = self.base_url.join(&format!("/novel/{}/catalog", book_id))?;
 
-        self.get_html(url.as_str(), message, 0).await
+        self.get_html(url.as_str(), message).await
     }
 
     pub async fn get_img_bytes(&self, url: &str, message: Option<&App>) -> Result<Vec<u8>> {
-        let mut client = self.client.get(url).header(
+        if let Some(cache) = &self.cache {
+            if let Some(data) = cache.get_bytes(url) {
+                return Ok(data);
+            }
+            if cache.is_offline() {
+                bail!(SiteError::CacheMiss {
+                    url: url.to_string()
+                });
+            }
+        }
+
+        let request_url = self.rewrite_url(url);
+        let mut client = self.image_client.get(&request_url).header(
             ACCEPT,
             "image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8",
         );
@@ -225,14 +499,24 @@ impl BiliClient {
                 HeaderValue::from_static("https://www.masiro.me/"),
             );
         }
-        let response = client.send().await?;
-        let data = response.bytes().await?;
+        let response = self.send(client, &request_url, RequestKind::Image).await?;
+        if response.status == 429 {
+            self.limiter.slow_down(&request_url, RequestKind::Image);
+        }
+        let data = response.body;
 
-        match utils::img_to_jpg(data.to_vec()) {
-            Ok(data) => Ok(data),
+        match utils::img_to_jpg(data.clone()) {
+            Ok(data) => {
+                if let Some(cache) = &self.cache
+                    && let Err(err) = cache.put_bytes(url, &data)
+                {
+                    send(message, &format!("写入缓存失败: {:?}", err));
+                }
+                Ok(data)
+            }
             Err(err) => {
                 if self.debug {
-                    send(message, String::from_utf8(data.to_vec())?.as_str());
+                    send(message, String::from_utf8_lossy(&data).as_ref());
                 }
                 Err(err)
             }
@@ -241,8 +525,10 @@ impl BiliClient {
 
     pub async fn check_update(&self) -> Result<String> {
         let url = "https://api.github.com/repos/kotorimiku/epub_download/releases/latest";
-        let res = self.client.get(url).send().await?;
-        let json = res.json::<serde_json::Value>().await?;
+        let res = self
+            .send(self.client.get(url), url, RequestKind::Page)
+            .await?;
+        let json = serde_json::from_slice::<serde_json::Value>(&res.body)?;
         let version = json["tag_name"]
             .as_str()
             .ok_or_else(|| err!("未获取到最新版本号"))?;
@@ -272,12 +558,130 @@ mod tests {
             &HashMap::new(),
             false,
             false,
-        );
+        )
+        .unwrap()
+        .with_recorder(Some(HttpRecorder::new(
+            HttpMode::Replay,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/replay"),
+        )));
         let result = client
-            .unwrap()
-            .get("https://www.bilinovel.com/novel/115/catalog")
+            .get("https://www.bilinovel.com/novel/9999/catalog")
+            .await
+            .unwrap();
+        assert!(result.contains("volume-chapters"));
+    }
+
+    #[tokio::test]
+    async fn test_offline_cache() {
+        let temp = tempfile::tempdir().unwrap();
+        let cache = PageCache::new(temp.path(), None, false);
+        cache
+            .put_bytes("https://img.example.com/1.jpg", b"jpg")
+            .unwrap();
+
+        let client = BiliClient::new(
+            "https://www.bilinovel.com",
+            "",
+            "",
+            &HashMap::new(),
+            false,
+            false,
+        )
+        .unwrap()
+        .with_cache(Some(PageCache::new(temp.path(), None, true)));
+        // 缓存中的图片不发起请求
+        assert_eq!(
+            client
+                .get_img_bytes("https://img.example.com/1.jpg", None)
+                .await
+                .unwrap(),
+            b"jpg"
+        );
+        for err in [
+            client
+                .get_img_bytes("https://img.example.com/2.jpg", None)
+                .await
+                .unwrap_err(),
+            client
+                .get_html("https://www.bilinovel.com/novel/1.html", None)
+                .await
+                .unwrap_err(),
+        ] {
+            assert!(matches!(
+                SiteError::find(&err),
+                Some(SiteError::CacheMiss { .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_follow_redirect() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let recorder = HttpRecorder::new(HttpMode::Record, dir);
+        let response = |url: &str, body: &str| HttpResponse {
+            url: url.to_string(),
+            status: 200,
+            body: body.as_bytes().to_vec(),
+        };
+        recorder
+            .save(
+                "https://www.bilinovel.com/novel/1/1.html",
+                &response("https://tw.bilinovel.com/novel/1/1.html", "第一页"),
+            )
+            .unwrap();
+        recorder
+            .save(
+                "https://tw.bilinovel.com/novel/1/2.html",
+                &response("https://tw.bilinovel.com/novel/1/2.html", "第二页"),
+            )
+            .unwrap();
+        recorder
+            .save(
+                "https://www.bilinovel.com/novel/1/3.html",
+                &response("https://www.bilinovel.com/login.php", "登录"),
+            )
+            .unwrap();
+
+        let client = BiliClient::new(
+            "https://www.bilinovel.com",
+            "",
+            "",
+            &HashMap::new(),
+            false,
+            false,
+        )
+        .unwrap()
+        .with_recorder(Some(HttpRecorder::new(HttpMode::Replay, dir)));
+        let html = client
+            .get_html("https://www.bilinovel.com/novel/1/1.html", None)
+            .await
+            .unwrap();
+        assert_eq!(html, "第一页");
+        // 之后的请求直接发往新域名
+        let html = client
+            .get_html("https://www.bilinovel.com/novel/1/2.html", None)
             .await
             .unwrap();
-        println!("{}", result);
+        assert_eq!(html, "第二页");
+
+        let client = BiliClient::new(
+            "https://www.bilinovel.com",
+            "",
+            "",
+            &HashMap::new(),
+            false,
+            false,
+        )
+        .unwrap()
+        .with_recorder(Some(HttpRecorder::new(HttpMode::Replay, dir)));
+        let err = client
+            .get_html("https://www.bilinovel.com/novel/1/3.html", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            SiteError::find(&err),
+            Some(SiteError::Redirected { .. })
+        ));
     }
 }
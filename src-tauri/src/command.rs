@@ -8,7 +8,9 @@ use crate::{
     config::Config,
     downloader::{Downloader, DownloaderConfig},
     error::CommandError,
-    model::{Book, BookInfo, VolumeInfo},
+    model::{Book, BookInfo, BookList, BookListKind, BookSummary, VolumeInfo},
+    queue::{DownloadQueue, QueueEntry, QueueMode, download_missing, run_queue},
+    source::{BiliSource, NovelSource},
 };
 
 type Result<T> = std::result::Result<T, CommandError>;
@@ -23,33 +25,53 @@ pub async fn get_book_info(
     app: AppHandle,
     book_id: String,
 ) -> Result<(BookInfo, Vec<VolumeInfo>)> {
-    let downloader_config = {
+    let (downloader_config, source) = {
         let config = config.read();
 
-        DownloaderConfig {
-            base_url: config.base_url.clone(),
-            book_id,
-            output: config.output.clone(),
-            template: config.template.clone(),
-            sleep_time: config.sleep_time,
-            convert_simple_chinese: config.convert_simple_chinese,
-            cookie: config.cookie.clone(),
-            user_agent: config.user_agent.clone(),
-            header_map: config.headers.clone(),
-            add_catalog: config.add_catalog,
-            error_img: config.error_img.clone(),
-            app_handle: Some(app),
-            debug: config.debug,
-        }
+        (
+            DownloaderConfig::from_config(&config, book_id, Some(app))?,
+            BiliSource::from_config(&config)?,
+        )
     }; // config 在这里自动 drop 释放锁
 
-    let result = Downloader::new(downloader_config).await?;
+    let result = Downloader::new(downloader_config, source).await?;
 
     let result = (result.book_info, result.volume_infos);
 
     Ok(result)
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn search_books(
+    config: State<'_, RwLock<Config>>,
+    app: AppHandle,
+    keyword: String,
+) -> Result<Vec<BookSummary>> {
+    let source = {
+        let config = config.read();
+        BiliSource::from_config(&config)?
+    };
+    let result = source.search(&keyword, Some(&app)).await?;
+    Ok(result)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn browse_books(
+    config: State<'_, RwLock<Config>>,
+    app: AppHandle,
+    kind: BookListKind,
+    page: u32,
+) -> Result<BookList> {
+    let source = {
+        let config = config.read();
+        BiliSource::from_config(&config)?
+    };
+    let result = source.fetch_book_list(kind, page, Some(&app)).await?;
+    Ok(result)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn download(
@@ -60,24 +82,15 @@ pub async fn download(
     book_info: BookInfo,
     volume_list: Vec<VolumeInfo>,
     volume_no_list: Vec<u32>,
+    update: bool,
+    omnibus: bool,
 ) -> Result<()> {
-    let downloader_config = {
+    let (downloader_config, source) = {
         let config = config.read();
-        DownloaderConfig {
-            base_url: config.base_url.clone(),
-            book_id,
-            output: config.output.clone(),
-            template: config.template.clone(),
-            sleep_time: config.sleep_time,
-            convert_simple_chinese: config.convert_simple_chinese,
-            cookie: config.cookie.clone(),
-            user_agent: config.user_agent.clone(),
-            header_map: config.headers.clone(),
-            add_catalog: config.add_catalog,
-            error_img: config.error_img.clone(),
-            app_handle: Some(app),
-            debug: config.debug,
-        }
+        (
+            DownloaderConfig::from_config(&config, book_id, Some(app))?,
+            BiliSource::from_config(&config)?,
+        )
     };
 
     // 创建取消接收器
@@ -86,8 +99,14 @@ pub async fn download(
     // 使用 tokio::select! 来处理下载任务和取消信号
     tokio::select! {
         result = async {
-            let downloader = Downloader::new_from(downloader_config, book_info, volume_list)?;
-            downloader.download(volume_no_list.into_iter()).await
+            let downloader = Downloader::new_from(downloader_config, source, book_info, volume_list);
+            if omnibus {
+                downloader.download_omnibus(volume_no_list.into_iter()).await
+            } else if update {
+                downloader.update(volume_no_list.into_iter()).await
+            } else {
+                downloader.download(volume_no_list.into_iter()).await
+            }
         } => {
             result?;
         }
@@ -99,6 +118,105 @@ pub async fn download(
     Ok(())
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn author_books(
+    config: State<'_, RwLock<Config>>,
+    app: AppHandle,
+    author: String,
+) -> Result<Vec<BookSummary>> {
+    let source = {
+        let config = config.read();
+        BiliSource::from_config(&config)?
+    };
+    let result = source.fetch_author_books(&author, Some(&app)).await?;
+    Ok(result)
+}
+
+/// 把作者所有作品中书库里还没有的卷加入下载队列并下载
+#[tauri::command]
+#[specta::specta]
+pub async fn download_author(
+    config: State<'_, RwLock<Config>>,
+    cancel_sender: State<'_, CancelSender>,
+    app: AppHandle,
+    author: String,
+) -> Result<()> {
+    let config = config.read().clone();
+    let source = BiliSource::from_config(&config)?;
+
+    let mut cancel_receiver = cancel_sender.subscribe();
+    tokio::select! {
+        result = async {
+            let books = source.fetch_author_books(&author, Some(&app)).await?;
+            download_missing(&config, books, Some(&app)).await
+        } => {
+            Ok(result?)
+        }
+        _ = cancel_receiver.recv() => {
+            // 已加入队列的任务下次运行时继续
+            DownloadQueue::load(&config.queue.file)?.interrupt()?;
+            Ok(())
+        }
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn queue_list(config: State<'_, RwLock<Config>>) -> Result<Vec<QueueEntry>> {
+    let file = config.read().queue.file.clone();
+    Ok(DownloadQueue::load(file)?.entries().to_vec())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn queue_add(
+    config: State<'_, RwLock<Config>>,
+    book_id: String,
+    title: Option<String>,
+    volume_no_list: Vec<u32>,
+    mode: QueueMode,
+) -> Result<QueueEntry> {
+    let file = config.read().queue.file.clone();
+    let volumes = volume_no_list
+        .iter()
+        .map(|no| no.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    Ok(DownloadQueue::load(file)?.add(book_id, title, volumes, mode)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn queue_remove(config: State<'_, RwLock<Config>>, id: u32) -> Result<()> {
+    let file = config.read().queue.file.clone();
+    DownloadQueue::load(file)?.remove(id)?;
+    Ok(())
+}
+
+/// 依次下载队列中的任务，返回失败的任务数
+#[tauri::command]
+#[specta::specta]
+pub async fn queue_run(
+    config: State<'_, RwLock<Config>>,
+    cancel_sender: State<'_, CancelSender>,
+    app: AppHandle,
+) -> Result<u32> {
+    let config = config.read().clone();
+
+    let mut cancel_receiver = cancel_sender.subscribe();
+    tokio::select! {
+        result = run_queue(&config, Some(&app)) => {
+            Ok(result? as u32)
+        }
+        _ = cancel_receiver.recv() => {
+            // 正在下载的任务下次运行时继续
+            DownloadQueue::load(&config.queue.file)?.interrupt()?;
+            Ok(0)
+        }
+    }
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn cancel_download(cancel_sender: State<'_, CancelSender>) -> Result<()> {
@@ -109,17 +227,21 @@ pub async fn cancel_download(cancel_sender: State<'_, CancelSender>) -> Result<(
 #[tauri::command]
 #[specta::specta]
 pub async fn browser_url(url: String, config: State<'_, RwLock<Config>>) -> Result<String> {
-    let (base_url, cookie, user_agent, header_map) = {
+    let (base_url, cookie, cookie_file, user_agent, header_map, proxy) = {
         let config = config.read();
         (
             config.base_url.clone(),
             config.cookie.clone(),
+            config.cookie_file.clone(),
             config.user_agent.clone(),
             config.headers.clone(),
+            config.proxy.clone(),
         )
     };
     let client =
-        crate::client::BiliClient::new(&base_url, &cookie, &user_agent, &header_map, false, false)?;
+        crate::client::BiliClient::new(&base_url, &cookie, &user_agent, &header_map, false, false)?
+            .with_proxy(&proxy)?
+            .with_cookie_file(&cookie_file)?;
     let result = client.get(&url).await?;
     Ok(result)
 }
@@ -141,7 +263,8 @@ pub async fn get_config_vue(config: State<'_, RwLock<Config>>) -> Result<Config>
 
 #[tauri::command]
 #[specta::specta]
-pub async fn check_update() -> Result<String> {
+pub async fn check_update(config: State<'_, RwLock<Config>>) -> Result<String> {
+    let proxy = config.read().proxy.clone();
     let client = crate::client::BiliClient::new(
         "https://www.bilinovel.com",
         "",
@@ -149,7 +272,8 @@ pub async fn check_update() -> Result<String> {
         &HashMap::new(),
         false,
         false,
-    )?;
+    )?
+    .with_proxy(&proxy)?;
     let result = client.check_update().await?;
     Ok(result)
 }
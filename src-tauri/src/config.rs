@@ -1,11 +1,22 @@
 use std::{
     collections::{HashMap, HashSet},
     fs::{read_to_string, write},
+    path::Path,
 };
 
-use crate::error::Result;
+use crate::{
+    cache::CacheConfig,
+    error::Result,
+    filter::FilterConfig,
+    proxy::ProxyConfig,
+    queue::QueueConfig,
+    rate_limit::{HostRateLimit, RateLimitConfig},
+    recorder::RecorderConfig,
+    redirect::RedirectConfig,
+    retry::RetryConfig,
+};
 
-const CONFIG_FILE: &str = "./config.json";
+pub const CONFIG_FILE: &str = "./config.json";
 pub const INDEX_FILE: &str = "./index.json";
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -18,12 +29,13 @@ pub struct Config {
     pub template: String,
     #[serde(default = "default_cookie")]
     pub cookie: String,
+    /// 保存cookie的文件，为空时不保存
+    #[serde(default = "default_cookie_file")]
+    pub cookie_file: String,
     #[serde(default = "default_user_agent")]
     pub user_agent: String,
     #[serde(default)]
     pub headers: HashMap<String, String>,
-    #[serde(default = "default_sleep_time")]
-    pub sleep_time: u32,
     #[serde(default = "default_base_url")]
     pub base_url: String,
     #[serde(default = "default_add_catalog")]
@@ -36,6 +48,37 @@ pub struct Config {
     pub convert_simple_chinese: bool,
     #[serde(default)]
     pub debug: bool,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub recorder: RecorderConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// 同时下载的插图数量
+    #[serde(default = "default_image_concurrency")]
+    pub image_concurrency: usize,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    #[serde(default)]
+    pub redirect: RedirectConfig,
+    /// 站点配置文件，为空时使用内置配置
+    #[serde(default)]
+    pub site_profile: String,
+    /// 广告、水印段落过滤规则
+    #[serde(default)]
+    pub filter: FilterConfig,
+    /// 保存下载断点的目录，中断后重新下载同一卷时从断点继续
+    #[serde(default = "default_checkpoint_dir")]
+    pub checkpoint_dir: String,
+    /// 下载队列
+    #[serde(default)]
+    pub queue: QueueConfig,
+    /// 旧版本的请求间隔（秒），读取时转换为页面限速，不再保存
+    #[serde(default, skip_serializing)]
+    #[cfg_attr(feature = "gui", specta(skip))]
+    pub sleep_time: Option<u32>,
 }
 
 impl Default for Config {
@@ -44,15 +87,27 @@ impl Default for Config {
             output: String::from("./"),
             template: "{{book_title}}-{{chapter_title}}".to_string(),
             cookie: String::from(""),
+            cookie_file: default_cookie_file(),
             user_agent: String::from(""),
             headers: HashMap::new(),
-            sleep_time: 8,
             base_url: String::from("https://www.bilinovel.com"),
             add_catalog: false,
             error_img: HashSet::new(),
             auto_check_update: true,
             convert_simple_chinese: false,
             debug: false,
+            cache: CacheConfig::default(),
+            recorder: RecorderConfig::default(),
+            retry: RetryConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            image_concurrency: default_image_concurrency(),
+            proxy: ProxyConfig::default(),
+            redirect: RedirectConfig::default(),
+            site_profile: String::new(),
+            filter: FilterConfig::default(),
+            checkpoint_dir: default_checkpoint_dir(),
+            queue: QueueConfig::default(),
+            sleep_time: None,
         }
     }
 }
@@ -64,11 +119,41 @@ impl Config {
     }
 
     pub fn load() -> Config {
-        read_to_string(CONFIG_FILE)
+        Self::load_file(CONFIG_FILE)
+    }
+
+    /// 读取配置文件，文件不存在或无效时使用默认配置
+    pub fn load_file(path: impl AsRef<Path>) -> Config {
+        read_to_string(path)
             .ok()
-            .and_then(|content| serde_json::from_str(&content).ok())
+            .and_then(|content| Self::parse(&content))
             .unwrap_or_default()
     }
+
+    fn parse(content: &str) -> Option<Config> {
+        let value: serde_json::Value = serde_json::from_str(content).ok()?;
+        let has_rate_limit = value.get("rateLimit").is_some();
+        let mut config: Config = serde_json::from_value(value).ok()?;
+        config.migrate(has_rate_limit);
+        Some(config)
+    }
+
+    /// 把旧版本的`sleepTime`转换为页面限速，每次请求间隔`sleepTime`秒，0 表示不限速
+    ///
+    /// 已经有`rateLimit`时以它为准；8 是旧版本的默认值，视为使用新的默认限速。
+    fn migrate(&mut self, has_rate_limit: bool) {
+        let Some(sleep_time) = self.sleep_time.take() else {
+            return;
+        };
+        if has_rate_limit || sleep_time == 8 {
+            return;
+        }
+        self.rate_limit.page = if sleep_time == 0 {
+            HostRateLimit::new(0.0, 1)
+        } else {
+            HostRateLimit::new(60.0 / sleep_time as f64, 1)
+        };
+    }
 }
 
 fn default_output() -> String {
@@ -83,12 +168,12 @@ fn default_cookie() -> String {
     String::from("")
 }
 
-fn default_user_agent() -> String {
-    String::from("")
+fn default_cookie_file() -> String {
+    String::from("./cookies.json")
 }
 
-fn default_sleep_time() -> u32 {
-    8
+fn default_user_agent() -> String {
+    String::from("")
 }
 
 fn default_base_url() -> String {
@@ -112,10 +197,52 @@ fn default_error_img() -> HashSet<String> {
     ERROR_IMG.iter().map(|s| s.to_string()).collect()
 }
 
+fn default_checkpoint_dir() -> String {
+    String::from("./checkpoint")
+}
+
 fn default_auto_check_update() -> bool {
     true
 }
 
+fn default_image_concurrency() -> usize {
+    4
+}
+
 fn default_convert_simple_chinese() -> bool {
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_sleep_time() {
+        let config = Config::parse(r#"{"sleepTime": 4}"#).unwrap();
+        assert_eq!(config.rate_limit.page.requests_per_minute, 15.0);
+        assert_eq!(config.rate_limit.page.burst, 1);
+        // 保存后不再包含旧字段
+        assert!(
+            !serde_json::to_string(&config)
+                .unwrap()
+                .contains("sleepTime")
+        );
+
+        let config = Config::parse("{}").unwrap();
+        assert_eq!(config.rate_limit.page.requests_per_minute, 8.0);
+
+        // 旧版本的默认值使用新的默认限速
+        let config = Config::parse(r#"{"sleepTime": 8}"#).unwrap();
+        assert_eq!(config.rate_limit.page.requests_per_minute, 8.0);
+        assert_eq!(config.rate_limit.page.burst, 3);
+
+        // 已有的限速设置不会被覆盖
+        let config = Config::parse(
+            r#"{"sleepTime": 4, "rateLimit": {"page": {"requestsPerMinute": 30, "burst": 2}}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.rate_limit.page.requests_per_minute, 30.0);
+        assert_eq!(config.rate_limit.page.burst, 2);
+    }
+}
@@ -0,0 +1,378 @@
+use std::{
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use chrono::DateTime;
+use cookie_store::{Cookie, CookieExpiration, CookieStore};
+use parking_lot::{Mutex, RwLock};
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{bail, err, error::Result, message::send};
+
+/// 可以持久化的cookie存储
+///
+/// 服务器返回的 `Set-Cookie` 会写入存储，设置了文件路径时同时保存到文件。
+/// 没有过期时间的会话cookie（例如配置中的cookie）只保存在内存中。
+#[derive(Default)]
+pub struct CookieJar {
+    store: RwLock<CookieStore>,
+    path: RwLock<Option<PathBuf>>,
+    /// 同一时间只有一个保存在写文件
+    save_lock: Mutex<()>,
+}
+
+/// 导入的一条cookie，字段与浏览器插件导出的json格式相同
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportedCookie {
+    domain: String,
+    name: String,
+    value: String,
+    #[serde(default = "default_path")]
+    path: String,
+    #[serde(default)]
+    host_only: bool,
+    #[serde(default)]
+    secure: bool,
+    #[serde(default)]
+    http_only: bool,
+    expiration_date: Option<f64>,
+}
+
+fn default_path() -> String {
+    String::from("/")
+}
+
+impl CookieJar {
+    /// 从文件读取cookie，之后的改动都会保存到该文件
+    ///
+    /// 已有的同名cookie不会被文件中的覆盖。
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if path.exists() {
+            let file = fs::File::open(path)?;
+            let loaded = cookie_store::serde::json::load(BufReader::new(file))
+                .map_err(|e| err!("读取cookie文件失败: {}", e))?;
+            let mut store = self.store.write();
+            for cookie in loaded.iter_unexpired() {
+                let domain = String::from(&cookie.domain);
+                if store.contains_any(&domain, &String::from(&cookie.path), cookie.name()) {
+                    continue;
+                }
+                if let Some(url) = domain_url(&domain, "/") {
+                    let _ = store.insert(cookie.clone(), &url);
+                }
+            }
+        }
+        *self.path.write() = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// 添加 `a=1; b=2` 形式的cookie，作为会话cookie不会保存到文件
+    pub fn add_cookie_str(&self, cookies: &str, url: &Url) {
+        let mut store = self.store.write();
+        for pair in cookies.split(';') {
+            let pair = pair.trim();
+            if pair.contains('=') {
+                let _ = store.parse(pair, url);
+            }
+        }
+    }
+
+    /// 从浏览器导出的 Netscape `cookies.txt` 或json文件导入，返回导入的数量
+    pub fn import(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let content = fs::read_to_string(path)?;
+        let trimmed = content.trim_start();
+        let cookies = if trimmed.starts_with('[') || trimmed.starts_with('{') {
+            parse_json(trimmed)?
+        } else {
+            parse_netscape(&content)
+        };
+        let cookies: Vec<_> = cookies.iter().filter_map(|c| c.to_set_cookie()).collect();
+        if cookies.is_empty() {
+            bail!("文件中没有可以导入的cookie");
+        }
+
+        let mut count = 0;
+        {
+            let mut store = self.store.write();
+            for (set_cookie, url) in cookies {
+                if store.parse(&set_cookie, &url).is_ok() {
+                    count += 1;
+                }
+            }
+        }
+        self.save()?;
+        Ok(count)
+    }
+
+    /// 以 Netscape `cookies.txt` 格式输出所有未过期的cookie
+    pub fn to_netscape(&self) -> String {
+        let store = self.store.read();
+        let mut lines = vec![String::from("# Netscape HTTP Cookie File")];
+        for cookie in store.iter_unexpired() {
+            lines.push(netscape_line(cookie));
+        }
+        lines.join("\n")
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        self.store.write().clear();
+        self.save()
+    }
+
+    /// 保存到文件，未设置文件路径时不做任何事
+    ///
+    /// 先写入同目录下的临时文件再改名，中途失败不会留下写了一半的文件。
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = self.path.read().clone() else {
+            return Ok(());
+        };
+        let _guard = self.save_lock.lock();
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut data = vec![];
+        cookie_store::serde::json::save(&self.store.read(), &mut data)
+            .map_err(|e| err!("保存cookie文件失败: {}", e))?;
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let mut changed = false;
+        {
+            let mut store = self.store.write();
+            for header in cookie_headers {
+                let Some(cookie) = header
+                    .to_str()
+                    .ok()
+                    .and_then(|value| Cookie::parse(value, url).ok())
+                else {
+                    continue;
+                };
+                // 只有值或有效期类型变化的持久cookie才需要重写文件
+                let existing = store
+                    .get(
+                        &String::from(&cookie.domain),
+                        &String::from(&cookie.path),
+                        cookie.name(),
+                    )
+                    .map(|c| (c.value().to_string(), c.is_persistent()));
+                let persistent = cookie.is_persistent();
+                let unchanged = existing
+                    .as_ref()
+                    .is_some_and(|(value, existing_persistent)| {
+                        value == cookie.value() && *existing_persistent == persistent
+                    });
+                if store.insert(cookie.into_owned(), url).is_ok()
+                    && !unchanged
+                    && (persistent || existing.is_some_and(|(_, p)| p))
+                {
+                    changed = true;
+                }
+            }
+        }
+        if changed && let Err(e) = self.save() {
+            send(None, &format!("保存cookie文件失败: {:?}", e));
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let cookie = self
+            .store
+            .read()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if cookie.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&cookie).ok()
+    }
+}
+
+fn domain_url(domain: &str, path: &str) -> Option<Url> {
+    Url::parse(&format!(
+        "https://{}{}",
+        domain.trim_start_matches('.'),
+        path
+    ))
+    .ok()
+}
+
+impl ImportedCookie {
+    /// 拼出 `Set-Cookie` 的值和对应的请求url
+    fn to_set_cookie(&self) -> Option<(String, Url)> {
+        let url = domain_url(&self.domain, &self.path)?;
+        let mut set_cookie = format!("{}={}; Path={}", self.name, self.value, self.path);
+        if !self.host_only {
+            set_cookie += &format!("; Domain={}", self.domain.trim_start_matches('.'));
+        }
+        if let Some(expires) = self.expiration_date {
+            let expires = DateTime::from_timestamp(expires as i64, 0)?;
+            set_cookie += &format!("; Expires={}", expires.format("%a, %d %b %Y %H:%M:%S GMT"));
+        }
+        if self.secure {
+            set_cookie += "; Secure";
+        }
+        if self.http_only {
+            set_cookie += "; HttpOnly";
+        }
+        Some((set_cookie, url))
+    }
+}
+
+fn parse_netscape(content: &str) -> Vec<ImportedCookie> {
+    let mut cookies = vec![];
+    for line in content.lines() {
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 {
+            continue;
+        }
+        cookies.push(ImportedCookie {
+            domain: fields[0].to_string(),
+            host_only: fields[1].eq_ignore_ascii_case("FALSE"),
+            path: fields[2].to_string(),
+            secure: fields[3].eq_ignore_ascii_case("TRUE"),
+            // 过期时间为0表示会话cookie
+            expiration_date: fields[4].trim().parse::<f64>().ok().filter(|&e| e > 0.0),
+            name: fields[5].to_string(),
+            value: fields[6].trim_end().to_string(),
+            http_only,
+        });
+    }
+    cookies
+}
+
+fn parse_json(content: &str) -> Result<Vec<ImportedCookie>> {
+    if content.starts_with('[') {
+        Ok(serde_json::from_str(content)?)
+    } else {
+        Ok(vec![serde_json::from_str(content)?])
+    }
+}
+
+fn netscape_line(cookie: &Cookie) -> String {
+    let domain = String::from(&cookie.domain);
+    let host_only = matches!(cookie.domain, cookie_store::CookieDomain::HostOnly(_));
+    let expires = match &cookie.expires {
+        CookieExpiration::AtUtc(time) => time.unix_timestamp(),
+        CookieExpiration::SessionEnd => 0,
+    };
+    format!(
+        "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        if cookie.http_only().unwrap_or(false) {
+            "#HttpOnly_"
+        } else {
+            ""
+        },
+        if host_only {
+            domain
+        } else {
+            format!(".{}", domain)
+        },
+        if host_only { "FALSE" } else { "TRUE" },
+        String::from(&cookie.path),
+        if cookie.secure().unwrap_or(false) {
+            "TRUE"
+        } else {
+            "FALSE"
+        },
+        expires,
+        cookie.name(),
+        cookie.value()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::cookie::CookieStore as _;
+
+    use super::*;
+
+    #[test]
+    fn test_import_and_persist() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let url = Url::parse("https://www.bilinovel.com/novel/1.html").unwrap();
+
+        let txt = dir.join("cookies.txt");
+        fs::write(
+            &txt,
+            "# Netscape HTTP Cookie File\n\
+             .bilinovel.com\tTRUE\t/\tTRUE\t4102444800\tcf_clearance\tabc\n\
+             #HttpOnly_www.bilinovel.com\tFALSE\t/\tFALSE\t4102444800\tPHPSESSID\txyz\n\
+             .bilinovel.com\tTRUE\t/\tFALSE\t1\texpired\t1\n",
+        )
+        .unwrap();
+        let json = dir.join("cookies.json");
+        fs::write(
+            &json,
+            r#"[{"domain":".example.com","name":"a","value":"1","expirationDate":4102444800.5}]"#,
+        )
+        .unwrap();
+
+        let file = dir.join("jar.json");
+        let jar = CookieJar::default();
+        jar.load_file(&file).unwrap();
+        assert_eq!(jar.import(&txt).unwrap(), 2);
+        assert_eq!(jar.import(&json).unwrap(), 1);
+        let cookie = jar.cookies(&url).unwrap();
+        let cookie = cookie.to_str().unwrap();
+        assert!(cookie.contains("cf_clearance=abc"));
+        assert!(cookie.contains("PHPSESSID=xyz"));
+        assert!(!cookie.contains("expired"));
+
+        // 服务器返回的cookie会写入文件，会话cookie不保存
+        let header = HeaderValue::from_static("night=1; Max-Age=3600");
+        jar.set_cookies(&mut std::iter::once(&header), &url);
+        jar.add_cookie_str("session=1", &url);
+        let saved = fs::read_to_string(&file).unwrap();
+        assert!(saved.contains("night"));
+        assert!(!dir.join("jar.json.tmp").exists());
+
+        // 值没有变化时不重写文件
+        fs::write(&file, "[]").unwrap();
+        jar.set_cookies(&mut std::iter::once(&header), &url);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "[]");
+        fs::write(&file, saved).unwrap();
+
+        let reloaded = CookieJar::default();
+        reloaded.load_file(&file).unwrap();
+        let cookie = reloaded.cookies(&url).unwrap();
+        let cookie = cookie.to_str().unwrap();
+        assert!(cookie.contains("night=1"));
+        assert!(cookie.contains("cf_clearance=abc"));
+        assert!(!cookie.contains("session"));
+        assert!(
+            reloaded
+                .to_netscape()
+                .contains("#HttpOnly_www.bilinovel.com\tFALSE")
+        );
+
+        reloaded.clear().unwrap();
+        let empty = CookieJar::default();
+        empty.load_file(&file).unwrap();
+        assert!(empty.cookies(&url).is_none());
+    }
+}
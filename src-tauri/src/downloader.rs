@@ -1,139 +1,150 @@
 use std::{
-    borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::HashSet,
     io::{self, Write},
     path::{self, PathBuf, absolute},
-    thread::sleep,
 };
 
+use color_eyre::eyre::WrapErr;
+use futures_util::{StreamExt, stream};
 use regex::Regex;
 
 use crate::{
     bail,
-    client::*,
-    epub_builder::{Body, ContentBlock, EpubBuilder, Metadata, MetadataConfig},
-    error::Result,
-    message::{self, print, send},
+    checkpoint::{ChapterCheckpoint, Checkpoint},
+    config::Config,
+    epub_builder::{Body, ContentBlock, EpubBuilder, Metadata, MetadataConfig, Section},
+    epub_reader::ExistingEpub,
+    error::{Result, SiteError},
+    filter::ContentFilter,
+    message::{self, send},
     model::{App, BookInfo, Content, VolumeInfo},
-    parse::{parse_metadata, parse_novel_text, parse_vol_desc, parse_volume_list},
-    runtime::{RUN_MODE, RunMode},
-    secret::decode_text,
+    progress::{self, DownloadEvent},
+    retry::{FailureClass, Retrier, RetryConfig},
+    source::NovelSource,
     utils::remove_invalid_chars,
 };
 
 pub struct DownloaderConfig {
-    pub base_url: String,
     pub book_id: String,
     pub output: String,
     pub template: String,
-    pub sleep_time: u32,
-    pub convert_simple_chinese: bool,
-    pub cookie: String,
-    pub user_agent: String,
-    pub header_map: HashMap<String, String>,
     pub add_catalog: bool,
     pub error_img: HashSet<String>,
     pub app_handle: Option<App>,
     pub debug: bool,
+    pub retry: RetryConfig,
+    /// 同时下载的插图数量
+    pub image_concurrency: usize,
+    pub filter: ContentFilter,
+    /// 保存下载断点的目录
+    pub checkpoint_dir: String,
 }
 
-pub struct Downloader {
-    pub base_url: String,
+impl DownloaderConfig {
+    pub fn from_config(config: &Config, book_id: String, app_handle: Option<App>) -> Result<Self> {
+        Ok(Self {
+            book_id,
+            output: config.output.clone(),
+            template: config.template.clone(),
+            add_catalog: config.add_catalog,
+            error_img: config.error_img.clone(),
+            app_handle,
+            debug: config.debug,
+            retry: config.retry.clone(),
+            image_concurrency: config.image_concurrency,
+            filter: ContentFilter::new(&config.filter)?,
+            checkpoint_dir: config.checkpoint_dir.clone(),
+        })
+    }
+}
+
+/// 下载完成的一卷，用于生成单卷或合集
+struct VolumePart {
+    chapters: Vec<Vec<Content>>,
+    /// 插图页已拆分为信息页和彩页
+    chapter_list: Vec<String>,
+    chapter_sources: Vec<String>,
+    /// 章节中的图片，不包括封面
+    image_urls: Vec<String>,
+    image_sources: Vec<String>,
+    cover: String,
+    cover_source: String,
+}
+
+pub struct Downloader<S: NovelSource> {
     pub book_id: String,
-    pub client: BiliClient,
+    pub source: S,
     pub book_info: BookInfo,
     pub volume_infos: Vec<VolumeInfo>,
     pub output: String,
     pub template: String,
-    pub sleep_time: u32,
     pub add_catalog: bool,
     pub error_img: HashSet<String>,
     pub app_handle: Option<App>,
     pub debug: bool,
+    pub retry: RetryConfig,
+    /// 同时下载的插图数量
+    pub image_concurrency: usize,
+    pub filter: ContentFilter,
+    pub checkpoint_dir: String,
 }
 
-async fn get_metadata(
-    book_id: &str,
-    client: &BiliClient,
-    app_handle: Option<&App>,
-) -> Result<BookInfo> {
-    Ok(parse_metadata(
-        &client.get_novel(book_id, app_handle).await?,
-    ))
-}
-
-async fn get_volume_list(
-    book_id: &str,
-    client: &BiliClient,
-    app_handle: Option<&App>,
-) -> Result<Vec<VolumeInfo>> {
-    Ok(parse_volume_list(
-        &client.get_catalog(book_id, app_handle).await?,
-    ))
-}
-
-impl Downloader {
-    pub async fn new(config: DownloaderConfig) -> Result<Self> {
-        let client = BiliClient::new(
-            &config.base_url,
-            &config.cookie,
-            &config.user_agent,
-            &config.header_map,
-            config.convert_simple_chinese,
-            config.debug,
-        )?;
-        let book_info = get_metadata(&config.book_id, &client, config.app_handle.as_ref()).await?;
+impl<S: NovelSource> Downloader<S> {
+    pub async fn new(config: DownloaderConfig, source: S) -> Result<Self> {
+        let book_info = source
+            .fetch_book_info(&config.book_id, config.app_handle.as_ref())
+            .await?;
         if book_info.title.is_none() {
-            bail!("Book not found");
+            bail!(SiteError::NotFound);
         }
-        let volume_infos =
-            get_volume_list(config.book_id.as_str(), &client, config.app_handle.as_ref()).await?;
-        Ok(Self {
-            base_url: config.base_url,
-            book_id: config.book_id,
-            client,
-            book_info,
-            volume_infos,
-            output: config.output,
-            template: config.template,
-            sleep_time: config.sleep_time,
-            add_catalog: config.add_catalog,
-            error_img: config.error_img,
-            app_handle: config.app_handle,
-            debug: config.debug,
-        })
+        let volume_infos = source
+            .fetch_volume_list(&config.book_id, config.app_handle.as_ref())
+            .await?;
+        Ok(Self::new_from(config, source, book_info, volume_infos))
     }
 
     pub fn new_from(
         config: DownloaderConfig,
+        source: S,
         book_info: BookInfo,
         volume_infos: Vec<VolumeInfo>,
-    ) -> Result<Self> {
-        let client = BiliClient::new(
-            &config.base_url,
-            &config.cookie,
-            &config.user_agent,
-            &config.header_map,
-            config.convert_simple_chinese,
-            config.debug,
-        )?;
-        Ok(Self {
-            base_url: config.base_url,
+    ) -> Self {
+        Self {
             book_id: config.book_id,
-            client,
+            source,
             book_info,
             volume_infos,
             output: config.output,
             template: config.template,
-            sleep_time: config.sleep_time,
             add_catalog: config.add_catalog,
             error_img: config.error_img,
             app_handle: config.app_handle,
             debug: config.debug,
-        })
+            retry: config.retry,
+            image_concurrency: config.image_concurrency,
+            filter: config.filter,
+            checkpoint_dir: config.checkpoint_dir,
+        }
     }
 
     pub async fn download<I>(&self, volume_no: I) -> Result<()>
+    where
+        I: Iterator<Item = u32>,
+        I: IntoIterator<Item = u32>,
+    {
+        self.run(volume_no, false).await
+    }
+
+    /// 更新已下载的卷，只下载新增或变化的章节
+    pub async fn update<I>(&self, volume_no: I) -> Result<()>
+    where
+        I: Iterator<Item = u32>,
+        I: IntoIterator<Item = u32>,
+    {
+        self.run(volume_no, true).await
+    }
+
+    async fn run<I>(&self, volume_no: I, update: bool) -> Result<()>
     where
         I: Iterator<Item = u32>,
         I: IntoIterator<Item = u32>,
@@ -147,18 +158,52 @@ impl Downloader {
             ),
         );
         io::stdout().flush().unwrap();
+        let mut last_err = None;
         for no in volume_no {
-            if let Some(volume) = self.volume_infos.get(no as usize - 1) {
-                match self.download_single(&mut volume.clone(), no as usize).await {
-                    Ok(()) => (),
-                    Err(err) => message::send(
-                        self.app_handle.as_ref(),
-                        &format!("下载第{}卷失败: {:?}", no, err),
-                    ),
+            let Some(volume) = self.volume_infos.get(no as usize - 1) else {
+                continue;
+            };
+            let result = if update {
+                self.update_single(&mut volume.clone(), no as usize).await
+            } else {
+                self.download_single(&mut volume.clone(), no as usize).await
+            };
+            if let Err(err) = result {
+                message::send(
+                    self.app_handle.as_ref(),
+                    &format!("下载第{}卷失败: {:?}", no, err),
+                );
+                self.emit(DownloadEvent::Failed {
+                    volume_no: no,
+                    error: err.to_string(),
+                });
+                // 书籍已删除、被限制访问等情况下，继续下载其他卷也会失败
+                if SiteError::find(&err)
+                    .is_some_and(|e| e.is_permanent() || matches!(e, SiteError::Blocked { .. }))
+                {
+                    return Err(err);
                 }
+                last_err = Some(err);
             }
         }
-        Ok(())
+        match last_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// 不在`downloaded`中的卷号
+    pub fn missing_volumes(&self, downloaded: &HashSet<String>) -> Vec<u32> {
+        self.volume_infos
+            .iter()
+            .zip(1..)
+            .filter(|(volume, _)| {
+                volume.url_vol.as_ref().is_none_or(|url| {
+                    !downloaded.contains(&url.replace(self.source.base_url(), ""))
+                })
+            })
+            .map(|(_, no)| no)
+            .collect()
     }
 
     async fn download_single(&self, volume: &mut VolumeInfo, volume_no: usize) -> Result<()> {
@@ -175,84 +220,23 @@ impl Downloader {
                 volume.title.as_ref().unwrap()
             ),
         );
+        let checkpoint = self.checkpoint(volume, volume_no);
+        let part = self.collect_volume(volume, volume_no, &checkpoint).await?;
+
         // 章节内容
-        let mut chapters_raw = Vec::new();
+        let mut chapters_raw = part.chapters;
         // 章节html
         let mut chapters = Vec::new();
-        // 图片url列表
-        let mut image_urls = Vec::new();
+        // 图片url列表，第一张为封面
+        let mut image_urls = vec![part.cover.clone()];
+        image_urls.extend(part.image_urls);
         // 图片扩展名列表
-        let mut image_exts = Vec::new();
+        let mut image_exts = vec![self.get_ext(&part.cover)];
         // 图片来源列表
-        let mut img_source_list = Vec::new();
-
-        let vol_desc = self.get_vol_desc(volume.url_vol.as_ref().unwrap()).await?;
-
-        let mut url = self.get_start_next_url(volume, volume_no).await?;
-        // let first_url = url.clone();
-
-        for i in 0..volume.chapter_list.len() {
-            send(
-                self.app_handle.as_ref(),
-                &format!("  -正在下载第{}章，{}", i + 1, volume.chapter_list[i]),
-            );
-            let mut chapter_text = Vec::new();
-            let next_url = self
-                .get_chapter_text(&url, &mut chapter_text, &mut image_urls)
-                .await?;
-            for _ in img_source_list.len()..image_urls.len() {
-                img_source_list.push(url.clone());
-            }
-            chapters_raw.push(chapter_text);
-            url = next_url;
-        }
-
-        // 添加封面
-        let mut add_cover = || {
-            let url = if volume.cover.is_some() {
-                volume.cover.clone().unwrap()
-            } else {
-                self.book_info.cover.clone().unwrap_or_default()
-            };
-            let ext = self.get_ext(&url);
-
-            image_urls.insert(0, url);
-            img_source_list.insert(0, self.base_url.clone());
-            image_exts.insert(0, ext);
-        };
-
-        if volume.chapter_list[0] == "插图" {
-            volume.chapter_list[0] = "彩页".to_string();
-            // 分离彩页
-            let color_page = chapters_raw.remove(0);
-            let (info, mut images): (Vec<_>, Vec<_>) = color_page
-                .into_iter()
-                .partition(|content| matches!(content, Content::Text(_) | Content::Tag(_)));
-            // 分离封面
-            if images.is_empty() {
-                send(self.app_handle.as_ref(), "  插图页无插图，删除插图页");
-                volume.chapter_list.remove(0);
-                // 添加封面
-                add_cover();
-            } else {
-                images.remove(0);
-                image_exts.push(self.get_ext(&image_urls[0]));
-                chapters_raw.insert(0, images);
-            }
-            // 添加信息页
-            let filter = info
-                .iter()
-                .filter(|content| !matches!(content, Content::Text(s) if s.is_empty()))
-                .filter(|content| !matches!(content, Content::Tag(s) if s.is_empty() || s.contains("<br")))
-                .collect::<Vec<_>>();
-            if !filter.is_empty() {
-                chapters_raw.insert(0, info);
-                volume.chapter_list.insert(0, "信息".to_string());
-            }
-        } else {
-            // 添加封面
-            add_cover();
-        }
+        let mut img_source_list = vec![part.cover_source];
+        img_source_list.extend(part.image_sources);
+        let mut chapter_list = part.chapter_list;
+        let mut chapter_sources = part.chapter_sources;
 
         self.get_chapters(
             &mut chapters_raw,
@@ -267,15 +251,17 @@ impl Downloader {
         for (i, chapter) in chapters.iter().enumerate() {
             if chapter
                 .iter()
-                .all(|cb| matches!(cb, ContentBlock::Text(s) if s.is_empty()))
+                .all(|cb| matches!(cb, ContentBlock::Content(content) if content.is_empty()))
             {
                 remove_list.push(i);
             }
         }
         for i in remove_list.iter().rev() {
             chapters.remove(*i);
-            volume.chapter_list.remove(*i);
+            chapter_list.remove(*i);
+            chapter_sources.remove(*i);
         }
+        let skipped_sources = skipped_sources(volume, &chapter_sources);
 
         if image_urls.len() != image_exts.len() {
             send(self.app_handle.as_ref(), "图片数量与扩展名数量不匹配");
@@ -293,43 +279,210 @@ impl Downloader {
 
         //下载插图
         let img_data_list = self
-            .download_img_list(&image_urls, &img_source_list)
+            .download_img_list(&image_urls, &img_source_list, &checkpoint)
             .await?;
 
         //制作epub
-        let title = format!(
-            "{}-{}",
-            self.book_info.title.as_ref().unwrap(),
-            volume.title.as_ref().unwrap()
+        let epub_builder = EpubBuilder::new(
+            self.build_metadata(volume, volume_no),
+            Body::Blocks(chapters),
+            chapter_list,
+            img_data_list,
+            image_exts,
+            image_urls,
+            self.add_catalog,
+        )
+        .with_chapter_sources(chapter_sources)
+        .with_skipped_sources(skipped_sources);
+
+        //保存文件
+        let path =
+            absolute(self.get_save_path(&volume_no.to_string(), volume.title.as_ref().unwrap())?)
+                .unwrap();
+        epub_builder.save_file(path.as_path())?;
+        self.emit(DownloadEvent::VolumeSaved {
+            path: path.display().to_string(),
+        });
+        checkpoint.clear()?;
+        send(
+            self.app_handle.as_ref(),
+            &format!("\n  下载完成，保存到: {}", &path.display()),
+        );
+        Ok(())
+    }
+
+    /// 把多卷合并为一个epub，每卷有自己的封面页，目录分为卷和章节两级
+    pub async fn download_omnibus<I>(&self, volume_no: I) -> Result<()>
+    where
+        I: Iterator<Item = u32>,
+        I: IntoIterator<Item = u32>,
+    {
+        let book_title = self.book_info.title.clone().unwrap_or_default();
+        send(
+            self.app_handle.as_ref(),
+            &format!("开始下载{}，{}，合并为一个文件", &self.book_id, book_title),
+        );
+        io::stdout().flush().unwrap();
+
+        let mut volumes = Vec::new();
+        let mut parts = Vec::new();
+        let mut checkpoints = Vec::new();
+        for no in volume_no {
+            let Some(volume) = self.volume_infos.get(no as usize - 1) else {
+                continue;
+            };
+            if volume.chapter_path_list.is_empty() {
+                continue;
+            }
+            let mut volume = volume.clone();
+            send(
+                self.app_handle.as_ref(),
+                &format!(
+                    " -正在下载第{}卷，{}",
+                    no,
+                    volume.title.as_deref().unwrap_or_default()
+                ),
+            );
+            let checkpoint = self.checkpoint(&volume, no as usize);
+            parts.push(
+                self.collect_volume(&mut volume, no as usize, &checkpoint)
+                    .await?,
+            );
+            volumes.push((no, volume));
+            checkpoints.push(checkpoint);
+        }
+        let (Some((first, _)), Some((last, _))) = (volumes.first(), volumes.last()) else {
+            bail!("没有可以下载的卷");
+        };
+        let label = if volumes.len() == self.volume_infos.len() {
+            format!("全{}卷", volumes.len())
+        } else {
+            format!("第{}-{}卷", first, last)
+        };
+        let range = format!("{}-{}", first, last);
+
+        // 图片按url去重，第一张为整本书的封面
+        let mut image_urls: Vec<String> = Vec::new();
+        let mut img_source_list = Vec::new();
+        let mut add_image = |url: &str, source: &str| {
+            image_urls.iter().position(|u| u == url).unwrap_or_else(|| {
+                image_urls.push(url.to_string());
+                img_source_list.push(source.to_string());
+                image_urls.len() - 1
+            })
+        };
+        add_image(&parts[0].cover, &parts[0].cover_source);
+
+        let mut chapters = Vec::new();
+        let mut chapter_list = Vec::new();
+        let mut chapter_sources = Vec::new();
+        let mut sections = Vec::new();
+        for ((_, volume), part) in volumes.iter().zip(parts) {
+            sections.push(Section {
+                title: volume.title.clone().unwrap_or_default(),
+                start: chapters.len(),
+            });
+            chapters.push(vec![ContentBlock::Image(add_image(
+                &part.cover,
+                &part.cover_source,
+            ))]);
+            chapter_list.push(String::from("封面"));
+            chapter_sources.push(volume.url_vol.clone().unwrap_or_default());
+
+            for ((raw, title), source) in part
+                .chapters
+                .into_iter()
+                .zip(part.chapter_list)
+                .zip(part.chapter_sources)
+            {
+                if raw.iter().all(|content| content.is_empty()) {
+                    continue;
+                }
+                let blocks = raw
+                    .into_iter()
+                    .map(|content| match content {
+                        Content::Image(url) => {
+                            let source = part
+                                .image_urls
+                                .iter()
+                                .position(|u| *u == url)
+                                .map_or(self.source.base_url(), |i| &part.image_sources[i]);
+                            let url = if url.starts_with("//") {
+                                format!("https:{}", url)
+                            } else {
+                                url
+                            };
+                            ContentBlock::Image(add_image(&url, source))
+                        }
+                        content => ContentBlock::Content(content),
+                    })
+                    .collect();
+                chapters.push(blocks);
+                chapter_list.push(title);
+                chapter_sources.push(source);
+            }
+        }
+        let image_exts = image_urls.iter().map(|url| self.get_ext(url)).collect();
+
+        let checkpoint = Checkpoint::new(
+            &self.checkpoint_dir,
+            &self.book_id,
+            &format!("omnibus_{}", range),
         );
-        let identifier = volume.url_vol.as_ref().unwrap().replace(&self.base_url, "");
-        let metadata_config = MetadataConfig {
+        let img_data_list = self
+            .download_img_list(&image_urls, &img_source_list, &checkpoint)
+            .await?;
+
+        let title = format!("{}-{}", book_title, label);
+        let date = volumes
+            .iter()
+            .filter_map(|(_, volume)| volume.last_update.as_deref())
+            .max();
+        let sum = |f: fn(&VolumeInfo) -> Option<u64>| {
+            volumes
+                .iter()
+                .map(|(_, volume)| f(volume))
+                .sum::<Option<u64>>()
+        };
+        let identifier = format!("/novel/{}/omnibus_{}.html", self.book_id, range);
+        let metadata = MetadataConfig {
             title: &title,
             creator: self.book_info.author.as_deref(),
+            illustrator: self.book_info.illustrator.as_deref(),
             publisher: self.book_info.publisher.as_deref(),
-            description: vol_desc.as_deref(),
+            description: self.book_info.description.as_deref(),
             series: self.book_info.title.as_deref(),
             subject: &self.book_info.tags,
             language: Some("zh-CN"),
-            index: Some(volume_no),
+            // 合集不属于系列中的某一卷
+            index: None,
             identifier: Some(&identifier),
+            date,
+            word_count: sum(|volume| volume.word_count),
+            chapter_count: sum(|volume| volume.chapter_count.map(u64::from))
+                .and_then(|count| count.try_into().ok()),
         };
-        let metadata: Metadata = metadata_config.into();
         let epub_builder = EpubBuilder::new(
-            metadata,
+            metadata.into(),
             Body::Blocks(chapters),
-            volume.chapter_list.clone(),
+            chapter_list,
             img_data_list,
             image_exts,
             image_urls,
             self.add_catalog,
-        );
+        )
+        .with_chapter_sources(chapter_sources)
+        .with_sections(sections);
 
-        //保存文件
-        let path =
-            absolute(self.get_save_path(&volume_no.to_string(), volume.title.as_ref().unwrap())?)
-                .unwrap();
+        let path = absolute(self.get_save_path(&range, &label)?).unwrap();
         epub_builder.save_file(path.as_path())?;
+        self.emit(DownloadEvent::VolumeSaved {
+            path: path.display().to_string(),
+        });
+        checkpoint.clear()?;
+        for checkpoint in checkpoints {
+            checkpoint.clear()?;
+        }
         send(
             self.app_handle.as_ref(),
             &format!("\n  下载完成，保存到: {}", &path.display()),
@@ -337,67 +490,423 @@ impl Downloader {
         Ok(())
     }
 
-    async fn get_vol_desc(&self, url: &str) -> Result<Option<String>> {
-        let url = if !url.starts_with("http") {
-            format!("{}{}", self.base_url, url).as_str().to_string()
-        } else {
-            url.to_string()
-        };
+    fn emit(&self, event: DownloadEvent) {
+        progress::emit(self.app_handle.as_ref(), event);
+    }
+
+    fn volume_started(&self, volume: &VolumeInfo, volume_no: usize) {
+        self.emit(DownloadEvent::VolumeStarted {
+            book_id: self.book_id.clone(),
+            volume_no: volume_no as u32,
+            title: volume.title.clone().unwrap_or_default(),
+            chapters: volume.chapter_list.len() as u32,
+        });
+    }
 
-        let html = self
-            .client
-            .get_html(&url, self.app_handle.as_ref(), 0)
+    /// 第`index`章下载完成，从0开始计数
+    fn chapter_fetched(&self, volume: &VolumeInfo, index: usize) {
+        self.emit(DownloadEvent::ChapterFetched {
+            i: index as u32 + 1,
+            n: volume.chapter_list.len() as u32,
+            title: volume.chapter_list[index].clone(),
+        });
+    }
+
+    fn checkpoint(&self, volume: &VolumeInfo, volume_no: usize) -> Checkpoint {
+        Checkpoint::new(
+            &self.checkpoint_dir,
+            &self.book_id,
+            &volume
+                .url_vol
+                .clone()
+                .unwrap_or_else(|| volume_no.to_string()),
+        )
+    }
+
+    /// 下载一卷的所有章节，插图页拆分为信息页和彩页，彩页的第一张图作为封面
+    async fn collect_volume(
+        &self,
+        volume: &mut VolumeInfo,
+        volume_no: usize,
+        checkpoint: &Checkpoint,
+    ) -> Result<VolumePart> {
+        // 章节内容
+        let mut chapters_raw = Vec::new();
+        // 图片url列表
+        let mut image_urls = Vec::new();
+        // 图片来源列表
+        let mut img_source_list = Vec::new();
+
+        self.source
+            .fetch_volume_detail(volume, self.app_handle.as_ref())
             .await?;
-        let desc = parse_vol_desc(&html);
-        Ok(desc)
-    }
-
-    async fn get_start_next_url(&self, volume: &VolumeInfo, volume_no: usize) -> Result<String> {
-        let mut next_url = self.base_url.clone() + &volume.chapter_path_list[0].clone();
-        if next_url.contains("javascript") {
-            let pre_volume = &self.volume_infos[volume_no - 2];
-            let pre_url_path = pre_volume.chapter_path_list.last().unwrap();
-            let url = self.base_url.clone() + pre_url_path;
-            next_url = self
-                .get_next_chapter_url(
-                    &self
-                        .client
-                        .get_html(&url, self.app_handle.as_ref(), self.sleep_time)
-                        .await?,
-                )
-                .await?;
+        self.volume_started(volume, volume_no);
+
+        // 第一章之前没有断点时才需要解析
+        let mut url = None;
+
+        for i in 0..volume.chapter_list.len() {
+            if let Some(saved) = checkpoint.load_chapter(i, &volume.chapter_list[i]) {
+                send(
+                    self.app_handle.as_ref(),
+                    &format!("  -第{}章已下载，{}", i + 1, volume.chapter_list[i]),
+                );
+                self.chapter_fetched(volume, i);
+                img_source_list.extend(saved.images.iter().map(|_| saved.url.clone()));
+                image_urls.extend(saved.images);
+                chapters_raw.push(saved.contents);
+                url = Some(saved.next_url);
+                continue;
+            }
+            let current_url = match url.take() {
+                Some(url) => url,
+                None => {
+                    self.source
+                        .resolve_start_url(&self.volume_infos, volume_no, self.app_handle.as_ref())
+                        .await?
+                }
+            };
+
+            send(
+                self.app_handle.as_ref(),
+                &format!("  -正在下载第{}章，{}", i + 1, volume.chapter_list[i]),
+            );
+            let mut chapter_text = Vec::new();
+            let mut chapter_images = Vec::new();
+            let next_url = self
+                .get_chapter_text(&current_url, &mut chapter_text, &mut chapter_images)
+                .await
+                .wrap_err_with(|| format!("第{}章下载失败，{}", i + 1, volume.chapter_list[i]))?;
+            self.filter_chapter(&mut chapter_text);
+            checkpoint.save_chapter(
+                i,
+                &ChapterCheckpoint {
+                    title: volume.chapter_list[i].clone(),
+                    contents: chapter_text.clone(),
+                    images: chapter_images.clone(),
+                    url: current_url.clone(),
+                    next_url: next_url.clone(),
+                },
+            )?;
+            self.chapter_fetched(volume, i);
+            img_source_list.extend(chapter_images.iter().map(|_| current_url.clone()));
+            image_urls.extend(chapter_images);
+            chapters_raw.push(chapter_text);
+            url = Some(next_url);
         }
-        Ok(next_url)
-    }
 
-    async fn get_next_chapter_url(&self, html: &str) -> Result<String> {
-        let mut current_html = html.to_string();
-        loop {
-            let url = self.get_next_url(&current_html)?;
-            if url.contains("_") {
-                current_html = self
-                    .client
-                    .get_html(&url, self.app_handle.as_ref(), self.sleep_time)
-                    .await?;
+        let mut chapter_list = volume.chapter_list.clone();
+        // 章节在目录中的链接，与chapter_list一一对应
+        let mut chapter_sources = volume.chapter_path_list.clone();
+        let mut cover = None;
+        if chapter_list[0] == "插图" {
+            chapter_list[0] = "彩页".to_string();
+            // 分离彩页
+            let color_page = chapters_raw.remove(0);
+            let (info, mut images): (Vec<_>, Vec<_>) = color_page
+                .into_iter()
+                .partition(|content| !matches!(content, Content::Image(_)));
+            // 分离封面
+            if images.is_empty() || image_urls.is_empty() {
+                send(self.app_handle.as_ref(), "  插图页无插图，删除插图页");
+                chapter_list.remove(0);
+                chapter_sources.remove(0);
             } else {
-                return Ok(url);
+                images.remove(0);
+                cover = Some((image_urls.remove(0), img_source_list.remove(0)));
+                chapters_raw.insert(0, images);
             }
+            // 添加信息页
+            if info.iter().any(|content| !content.is_empty()) {
+                chapters_raw.insert(0, info);
+                chapter_list.insert(0, "信息".to_string());
+                chapter_sources.insert(0, volume.chapter_path_list[0].clone());
+            }
+        }
+        let (cover, cover_source) = cover.unwrap_or_else(|| {
+            let url = volume
+                .cover
+                .clone()
+                .or_else(|| self.book_info.cover.clone())
+                .unwrap_or_default();
+            (url, self.source.base_url().to_string())
+        });
+
+        Ok(VolumePart {
+            chapters: chapters_raw,
+            chapter_list,
+            chapter_sources,
+            image_urls,
+            image_sources: img_source_list,
+            cover,
+            cover_source,
+        })
+    }
+
+    /// 读取已下载的epub，保留目录中标题和链接都没有变化的章节，只下载新增或变化的章节
+    async fn update_single(&self, volume: &mut VolumeInfo, volume_no: usize) -> Result<()> {
+        let path =
+            absolute(self.get_save_path(&volume_no.to_string(), volume.title.as_ref().unwrap())?)
+                .unwrap();
+        if !path.exists() {
+            send(
+                self.app_handle.as_ref(),
+                &format!(" -第{}卷还没有下载，下载整卷", volume_no),
+            );
+            return self.download_single(volume, volume_no).await;
+        }
+        if volume.chapter_path_list.is_empty() {
+            send(self.app_handle.as_ref(), "章节列表为空");
+            return Ok(());
+        }
+        let existing = ExistingEpub::open(&path)?;
+        let identifier = self.identifier(volume);
+        if existing.identifier.as_deref() != Some(identifier.as_str()) {
+            bail!("{} 不是第{}卷的文件，无法更新", path.display(), volume_no);
+        }
+
+        send(
+            self.app_handle.as_ref(),
+            &format!(
+                " -正在更新第{}卷，{}",
+                volume_no,
+                volume.title.as_ref().unwrap()
+            ),
+        );
+        self.source
+            .fetch_volume_detail(volume, self.app_handle.as_ref())
+            .await?;
+        self.volume_started(volume, volume_no);
+
+        /// 更新后的一章
+        enum Chapter {
+            /// 保留的旧章节xhtml
+            Kept(String),
+            /// chapters_raw中的索引
+            New(usize),
+        }
+        let mut chapter_list = Vec::new();
+        let mut chapter_sources = Vec::new();
+        let mut updated = Vec::new();
+        let mut chapters_raw = Vec::new();
+        let mut image_urls = Vec::new();
+        let mut img_source_list = Vec::new();
+        // 上一章刚下载时已知的下一章链接
+        let mut next_url = None;
+
+        for i in 0..volume.chapter_list.len() {
+            let title = &volume.chapter_list[i];
+            let source = &volume.chapter_path_list[i];
+            // 插图页在下载时被拆分为信息页和彩页
+            let kept: Vec<_> = existing
+                .chapters
+                .iter()
+                .filter(|chapter| {
+                    let same_title = if title == "插图" {
+                        chapter.title == "信息" || chapter.title == "彩页"
+                    } else {
+                        &chapter.title == title
+                    };
+                    same_title && chapter.source.as_ref().is_none_or(|s| s == source)
+                })
+                .collect();
+            if !kept.is_empty() {
+                for chapter in kept {
+                    chapter_list.push(chapter.title.clone());
+                    chapter_sources.push(source.clone());
+                    updated.push(Chapter::Kept(chapter.body.clone()));
+                }
+                self.chapter_fetched(volume, i);
+                next_url = None;
+                continue;
+            }
+            // 上次下载时内容为空，不再重复下载
+            if existing.skipped.contains(source) {
+                self.chapter_fetched(volume, i);
+                next_url = None;
+                continue;
+            }
+
+            send(
+                self.app_handle.as_ref(),
+                &format!("  -正在下载第{}章，{}", i + 1, title),
+            );
+            let url = self
+                .chapter_url(volume, volume_no, i, next_url.take())
+                .await?;
+            let mut chapter_text = Vec::new();
+            let mut chapter_images = Vec::new();
+            next_url = Some(
+                self.get_chapter_text(&url, &mut chapter_text, &mut chapter_images)
+                    .await
+                    .wrap_err_with(|| format!("第{}章下载失败，{}", i + 1, title))?,
+            );
+            self.filter_chapter(&mut chapter_text);
+            self.chapter_fetched(volume, i);
+            if chapter_text.iter().all(|content| content.is_empty()) {
+                continue;
+            }
+            img_source_list.extend(chapter_images.iter().map(|_| url.clone()));
+            image_urls.extend(chapter_images);
+            chapter_list.push(if title == "插图" {
+                String::from("彩页")
+            } else {
+                title.clone()
+            });
+            chapter_sources.push(source.clone());
+            updated.push(Chapter::New(chapters_raw.len()));
+            chapters_raw.push(chapter_text);
+        }
+
+        if chapters_raw.is_empty() && chapter_list.len() == existing.chapters.len() {
+            send(self.app_handle.as_ref(), "  没有新章节");
+            return Ok(());
         }
+
+        let mut new_chapters = Vec::new();
+        let mut image_exts = Vec::new();
+        self.get_chapters(
+            &mut chapters_raw,
+            &mut image_urls,
+            &mut new_chapters,
+            &mut image_exts,
+            &mut img_source_list,
+        );
+        let checkpoint = Checkpoint::new(&self.checkpoint_dir, &self.book_id, &identifier);
+        let new_images = self
+            .download_img_list(&image_urls, &img_source_list, &checkpoint)
+            .await?;
+
+        // 新插图排在已有插图之后，旧章节中的图片路径保持不变
+        let offset = existing.images.len();
+        let mut new_chapters: Vec<_> = new_chapters.into_iter().map(Some).collect();
+        let chapters = updated
+            .into_iter()
+            .map(|chapter| match chapter {
+                Chapter::Kept(html) => vec![ContentBlock::Html(html)],
+                Chapter::New(index) => new_chapters[index]
+                    .take()
+                    .unwrap()
+                    .into_iter()
+                    .map(|block| match block {
+                        ContentBlock::Image(image) => ContentBlock::Image(image + offset),
+                        block => block,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let mut images = Vec::new();
+        let mut exts = Vec::new();
+        for image in existing.images {
+            images.push(image.data);
+            exts.push(image.ext);
+        }
+        let mut alts = vec![String::new(); offset];
+        images.extend(new_images);
+        exts.extend(image_exts);
+        alts.extend(image_urls);
+
+        let epub_builder = EpubBuilder::new(
+            self.build_metadata(volume, volume_no),
+            Body::Blocks(chapters),
+            chapter_list,
+            images,
+            exts,
+            alts,
+            self.add_catalog,
+        )
+        .with_skipped_sources(skipped_sources(volume, &chapter_sources))
+        .with_chapter_sources(chapter_sources);
+        epub_builder.save_file(path.as_path())?;
+        self.emit(DownloadEvent::VolumeSaved {
+            path: path.display().to_string(),
+        });
+        checkpoint.clear()?;
+        send(
+            self.app_handle.as_ref(),
+            &format!(
+                "\n  更新完成，新增或更新{}章，保存到: {}",
+                chapters_raw.len(),
+                &path.display()
+            ),
+        );
+        Ok(())
     }
 
-    fn get_next_url(&self, html: &str) -> Result<String> {
-        let re = Regex::new(r"url_next:'(.+?)'").unwrap();
-        // 使用正则表达式进行匹配
-        if let Some(captures) = re.captures(html) {
-            // 提取匹配到的第一个分组（即 URL）
-            if let Some(url) = captures.get(1) {
-                return Ok(self.base_url.clone() + url.as_str());
+    /// 第`index`章的链接，目录中没有链接时沿着前面章节的下一章链接查找
+    async fn chapter_url(
+        &self,
+        volume: &VolumeInfo,
+        volume_no: usize,
+        index: usize,
+        next_url: Option<String>,
+    ) -> Result<String> {
+        if let Some(url) = next_url {
+            return Ok(url);
+        }
+        let mut start = index;
+        while start > 0 && volume.chapter_path_list[start].contains("javascript") {
+            start -= 1;
+        }
+        let mut url = if start == 0 {
+            self.source
+                .resolve_start_url(&self.volume_infos, volume_no, self.app_handle.as_ref())
+                .await?
+        } else {
+            format!(
+                "{}{}",
+                self.source.base_url(),
+                volume.chapter_path_list[start]
+            )
+        };
+        for _ in start..index {
+            let mut page = self
+                .source
+                .fetch_chapter_page(&url, self.app_handle.as_ref())
+                .await?;
+            while page.has_next_page {
+                page = self
+                    .source
+                    .fetch_chapter_page(&page.next_url, self.app_handle.as_ref())
+                    .await?;
             }
+            url = page.next_url;
         }
+        Ok(url)
+    }
 
-        send(self.app_handle.as_ref(), "寻找章节链接失败");
-        println!("{}", html);
-        bail!("寻找章节链接失败")
+    /// epub中的标识符，即不含站点地址的卷链接
+    fn identifier(&self, volume: &VolumeInfo) -> String {
+        volume
+            .url_vol
+            .as_ref()
+            .unwrap()
+            .replace(self.source.base_url(), "")
+    }
+
+    fn build_metadata(&self, volume: &VolumeInfo, volume_no: usize) -> Metadata {
+        let title = format!(
+            "{}-{}",
+            self.book_info.title.as_ref().unwrap(),
+            volume.title.as_ref().unwrap()
+        );
+        let identifier = self.identifier(volume);
+        MetadataConfig {
+            title: &title,
+            creator: self.book_info.author.as_deref(),
+            illustrator: self.book_info.illustrator.as_deref(),
+            publisher: self.book_info.publisher.as_deref(),
+            description: volume.description.as_deref(),
+            series: self.book_info.title.as_deref(),
+            subject: &self.book_info.tags,
+            language: Some("zh-CN"),
+            index: Some(volume_no),
+            identifier: Some(&identifier),
+            date: volume.last_update.as_deref(),
+            word_count: volume.word_count,
+            chapter_count: volume.chapter_count,
+        }
+        .into()
     }
 
     fn get_save_path(&self, volume_no: &str, title: &str) -> Result<PathBuf> {
@@ -439,70 +948,83 @@ impl Downloader {
         &self,
         img_url_list: &[String],
         img_source_list: &[String],
+        checkpoint: &Checkpoint,
     ) -> Result<Vec<Vec<u8>>> {
         send(self.app_handle.as_ref(), "  正在下载插图");
 
-        let mut img_data_list = Vec::new();
-        for i in 0..img_url_list.len() {
-            let mut img_data = Vec::new();
-            let mut error_img = false;
-            for _ in 0..50 {
-                match self
-                    .client
-                    .get_img_bytes(&img_url_list[i], self.app_handle.as_ref())
-                    .await
-                {
-                    Ok(data) => {
-                        img_data = data;
-                        break;
-                    }
-                    Err(err) => {
-                        message::send(
-                            self.app_handle.as_ref(),
-                            &format!("  插图下载失败: {:?}", err),
-                        );
-                    }
-                };
+        // buffered 按输入顺序返回结果，同时最多下载 image_concurrency 张
+        let mut images = stream::iter(img_url_list.iter().zip(img_source_list))
+            .map(|(url, source)| self.download_img_with_checkpoint(url, source, checkpoint))
+            .buffered(self.image_concurrency.max(1));
+
+        let mut img_data_list = Vec::with_capacity(img_url_list.len());
+        while let Some(img_data) = images.next().await {
+            img_data_list.push(img_data?);
+            self.emit(DownloadEvent::ImageFetched {
+                i: img_data_list.len() as u32,
+                n: img_url_list.len() as u32,
+            });
+        }
+        Ok(img_data_list)
+    }
+
+    /// 优先使用断点中的插图，下载成功后写入断点
+    async fn download_img_with_checkpoint(
+        &self,
+        img_url: &str,
+        img_source: &str,
+        checkpoint: &Checkpoint,
+    ) -> Result<Vec<u8>> {
+        if let Some(data) = checkpoint.load_image(img_url) {
+            return Ok(data);
+        }
+        let data = self.download_img(img_url, img_source).await?;
+        if !data.is_empty() {
+            checkpoint.save_image(img_url, &data)?;
+        }
+        Ok(data)
+    }
 
-                if self.error_img.contains(&img_url_list[i]) {
-                    send(
+    /// 下载一张插图，失败时按策略重试
+    async fn download_img(&self, img_url: &str, img_source: &str) -> Result<Vec<u8>> {
+        let mut retrier = Retrier::new(&self.retry);
+        loop {
+            match self
+                .source
+                .fetch_image(img_url, self.app_handle.as_ref())
+                .await
+            {
+                Ok(data) if !data.is_empty() => return Ok(data),
+                Ok(_) => {
+                    message::send(self.app_handle.as_ref(), "  插图下载失败: 图片为空");
+                }
+                Err(err) => {
+                    message::send(
                         self.app_handle.as_ref(),
-                        &format!("\n  错误图片，跳过: {}", img_url_list[i]),
+                        &format!("  插图下载失败: {:?}", err),
                     );
-                    error_img = true;
-                    break;
                 }
+            };
 
-                send(self.app_handle.as_ref(), "\n  插图下载失败，正在重试");
+            if self.error_img.contains(img_url) {
                 send(
                     self.app_handle.as_ref(),
-                    &format!("  {}", img_source_list[i]),
+                    &format!("\n  错误图片，跳过: {}", img_url),
                 );
-                send(self.app_handle.as_ref(), &format!("  {}", img_url_list[i]));
-                sleep(std::time::Duration::from_secs(5));
-            }
-
-            if error_img {
                 // 使用一张空白图片占位，避免epub制作失败
-                img_data_list.push(img_data);
-                continue;
+                return Ok(Vec::new());
             }
 
-            if img_data.is_empty() {
-                bail!("插图下载失败,{},{}", img_url_list[i], img_source_list[i]);
-            }
-
-            img_data_list.push(img_data);
-
-            // 进度
-            print(
-                self.app_handle.as_ref(),
-                &format!("\r  Progress: {}/{}", i + 1, img_url_list.len()),
-            ); // 使用 \r 覆盖同一行
-
-            io::stdout().flush().unwrap(); // 强制刷新缓冲区
+            send(self.app_handle.as_ref(), &format!("  {}", img_source));
+            send(self.app_handle.as_ref(), &format!("  {}", img_url));
+            retrier
+                .wait(
+                    FailureClass::Image,
+                    &format!("插图下载失败,{},{}", img_url, img_source),
+                    self.app_handle.as_ref(),
+                )
+                .await?;
         }
-        Ok(img_data_list)
     }
 
     fn get_ext(&self, url: &str) -> String {
@@ -547,14 +1069,35 @@ impl Downloader {
                             image_exts.push(ext);
                         }
                     }
-                    Content::Text(text) => chapter.push(ContentBlock::Text(text.to_owned())),
-                    Content::Tag(tag) => chapter.push(ContentBlock::Tag(tag.to_owned())),
+                    content => chapter.push(ContentBlock::Content(content.to_owned())),
                 }
             }
             chapters.push(chapter);
         }
     }
 
+    /// 删除广告、水印段落，预览模式下只输出将被删除的段落
+    fn filter_chapter(&self, chapter_text: &mut Vec<Content>) {
+        let removed = self.filter.apply(chapter_text);
+        if removed.is_empty() {
+            return;
+        }
+        if self.filter.is_dry_run() {
+            send(
+                self.app_handle.as_ref(),
+                &format!("   过滤预览，将删除{}段：", removed.len()),
+            );
+            for text in removed {
+                send(self.app_handle.as_ref(), &format!("     - {}", text));
+            }
+        } else {
+            send(
+                self.app_handle.as_ref(),
+                &format!("   已过滤{}段", removed.len()),
+            );
+        }
+    }
+
     /// 返回下一章节url
     async fn get_chapter_text(
         &self,
@@ -562,161 +1105,298 @@ impl Downloader {
         chapter_text: &mut Vec<Content>,
         img_list: &mut Vec<String>,
     ) -> Result<String> {
-        let html = self
-            .client
-            .get_html(url, self.app_handle.as_ref(), self.sleep_time)
+        let mut page = self
+            .source
+            .fetch_chapter_page(url, self.app_handle.as_ref())
             .await?;
+        chapter_text.extend(page.contents);
+        img_list.extend(page.images);
 
-        let chapter = self.paragraph_restorer(&html, img_list, url)?;
-
-        chapter_text.extend(chapter);
-
-        // 文本解密
-        if html.contains(r#"font-family: "read""#) {
-            for content in &mut chapter_text.iter_mut().rev() {
-                if let Content::Text(text) = content {
-                    if text.contains("<br") || text.is_empty() {
-                        continue;
-                    }
-                    let new_text = decode_text(text);
-                    println!("解密前: {}", text);
-                    println!("解密后: {}", new_text);
-                    *text = new_text;
-                    break;
-                }
-                if let Content::Tag(tag) = content {
-                    if tag.contains("<br") || tag.is_empty() {
-                        continue;
-                    }
-                    let new_tag = decode_text(tag);
-                    println!("解密前: {}", tag);
-                    println!("解密后: {}", new_tag);
-                    *tag = new_tag;
-                    break;
-                }
-            }
-        }
-
-        let mut current_url = self.get_next_url(&html)?;
-        while current_url.contains("_") {
+        while page.has_next_page {
             send(self.app_handle.as_ref(), "   正在下载分页");
-            let html = self
-                .client
-                .get_html(&current_url, self.app_handle.as_ref(), self.sleep_time)
+            page = self
+                .source
+                .fetch_chapter_page(&page.next_url, self.app_handle.as_ref())
                 .await?;
-
-            let chapter = self.paragraph_restorer(&html, img_list, &current_url)?;
-
-            chapter_text.extend(chapter);
-
-            current_url = self.get_next_url(&html)?;
+            chapter_text.extend(page.contents);
+            img_list.extend(page.images);
         }
-        Ok(current_url)
+        Ok(page.next_url)
     }
+}
 
-    fn paragraph_restorer(
-        &self,
-        html: &str,
-        img_list: &mut Vec<String>,
-        _url: &str,
-    ) -> Result<Vec<Content>> {
-        // #[cfg(feature = "gui")]
-        // let html = &crate::event::html(self.app_handle.as_ref().unwrap(), html)?;
-
-        let html = match *RUN_MODE.lock() {
-            RunMode::Gui => {
-                #[cfg(feature = "gui")]
-                {
-                    match crate::event::html(self.app_handle.as_ref().unwrap(), html) {
-                        Ok(html) => Cow::Owned(html),
-                        Err(err) => {
-                            if self.debug {
-                                send(self.app_handle.as_ref(), html);
-                            }
-                            bail!("章节内容解析失败: {:?}", err);
-                        }
-                    }
-                }
-
-                #[cfg(not(feature = "gui"))]
-                bail!("当前构建未启用 gui feature");
-            }
-            RunMode::Cli => Cow::Borrowed(html),
-        };
+/// 目录中有但没有收录的章节，例如内容为空的章节
+///
+/// 由`javascript:`补全链接的章节不记录，这些链接不能区分章节。
+fn skipped_sources(volume: &VolumeInfo, chapter_sources: &[String]) -> Vec<String> {
+    volume
+        .chapter_path_list
+        .iter()
+        .filter(|source| !source.contains("javascript") && !chapter_sources.contains(source))
+        .cloned()
+        .collect()
+}
 
-        let mut chapter = Vec::new();
-        parse_novel_text(html.as_ref(), &mut chapter, img_list, &self.base_url);
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read};
 
-        if chapter.is_empty() {
-            send(self.app_handle.as_ref(), "   章节内容为空");
-            println!("{}", html);
-            bail!("章节内容为空");
-        }
+    use zip::ZipArchive;
 
-        let chapter = match *RUN_MODE.lock() {
-            RunMode::Gui => chapter,
-            RunMode::Cli => {
-                use crate::paragraph_restorer::ParagraphRestorer;
-                if Self::get_chapterlog_version(html.as_ref())? != ParagraphRestorer::get_version()
-                {
-                    bail!("章节日志版本不匹配，无法恢复章节顺序");
-                }
-                let chapter_id = _url
-                    .split("/")
-                    .last()
-                    .unwrap()
-                    .split(".")
-                    .next()
-                    .unwrap()
-                    .split("_")
-                    .next()
-                    .unwrap()
-                    .parse::<u64>()
-                    .unwrap();
-
-                let restorer = ParagraphRestorer::new(chapter_id);
-                restorer.restore(chapter)
-            }
+    use super::*;
+    use crate::{
+        config::Config,
+        filter::{FilterConfig, FilterRule, FilterScope},
+        recorder::{HttpMode, RecorderConfig},
+        runtime::{RUN_MODE, RunMode},
+        source::BiliSource,
+    };
+
+    async fn replay_downloader(output: &path::Path) -> Downloader<BiliSource> {
+        *RUN_MODE.lock() = RunMode::Cli;
+
+        let config = Config {
+            recorder: RecorderConfig {
+                mode: HttpMode::Replay,
+                dir: concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/replay").to_string(),
+            },
+            ..Config::default()
         };
+        Downloader::new(
+            DownloaderConfig {
+                book_id: "9999".to_string(),
+                output: output.to_string_lossy().to_string(),
+                template: config.template.clone(),
+                add_catalog: config.add_catalog,
+                error_img: config.error_img.clone(),
+                app_handle: None,
+                debug: config.debug,
+                retry: config.retry.clone(),
+                image_concurrency: config.image_concurrency,
+                filter: ContentFilter::new(&config.filter).unwrap(),
+                checkpoint_dir: output.join("checkpoint").to_string_lossy().to_string(),
+            },
+            BiliSource::from_config(&config).unwrap(),
+        )
+        .await
+        .unwrap()
+    }
 
-        Ok(chapter)
+    fn read_chapter(output: &path::Path, name: &str) -> String {
+        let path = output.join("测试小说").join("测试小说-第一卷.epub");
+        let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        let mut chapter = String::new();
+        zip.by_name(name)
+            .unwrap()
+            .read_to_string(&mut chapter)
+            .unwrap();
+        chapter
     }
 
-    fn get_chapterlog_version(html: &str) -> Result<String> {
-        let re = Regex::new(r"chapterlog\.js\?v([\w.]+)").unwrap();
-        if let Some(captures) = re.captures(html)
-            && let Some(version) = captures.get(0)
-        {
-            return Ok(version.as_str().to_string());
-        }
+    #[tokio::test]
+    async fn test_download_replay() {
+        let temp = tempfile::tempdir().unwrap();
+        let output = temp.path();
+        let downloader = replay_downloader(output).await;
+        assert_eq!(downloader.book_info.title.as_deref(), Some("测试小说"));
+        assert_eq!(downloader.volume_infos.len(), 2);
+
+        downloader.download(1..=1).await.unwrap();
+
+        let chapter = read_chapter(output, "OEBPS/Text/001.xhtml");
+        assert!(chapter.contains("第一章的第一段。"));
+        assert!(chapter.contains("第一章分页的内容。"));
+        // 封面和第一章插图
+        let path = output.join("测试小说").join("测试小说-第一卷.epub");
+        let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        assert!(zip.by_name("OEBPS/Images/000.png").is_ok());
+        assert!(zip.by_name("OEBPS/Images/001.png").is_ok());
+        // 已下载的卷不再下载
+        let downloaded = crate::manage::downloaded_volumes(output.to_str().unwrap()).unwrap();
+        assert_eq!(downloader.missing_volumes(&downloaded), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_download_resume() {
+        let temp = tempfile::tempdir().unwrap();
+        let output = temp.path();
+        let downloader = replay_downloader(output).await;
+
+        // 上次下载到第一章后中断
+        let volume = &downloader.volume_infos[0];
+        let checkpoint = Checkpoint::new(
+            &downloader.checkpoint_dir,
+            "9999",
+            volume.url_vol.as_ref().unwrap(),
+        );
+        checkpoint
+            .save_chapter(
+                0,
+                &ChapterCheckpoint {
+                    title: volume.chapter_list[0].clone(),
+                    contents: vec![Content::paragraph("断点中的第一章。")],
+                    images: Vec::new(),
+                    url: String::from("https://www.bilinovel.com/novel/9999/1001.html"),
+                    next_url: String::from("https://www.bilinovel.com/novel/9999/1002.html"),
+                },
+            )
+            .unwrap();
 
-        bail!("chapterlog.js version not found")
+        downloader.download(1..=1).await.unwrap();
+
+        let chapter = read_chapter(output, "OEBPS/Text/001.xhtml");
+        assert!(chapter.contains("断点中的第一章。"));
+        assert!(!chapter.contains("第一章的第一段。"));
+        // 生成epub后删除断点
+        assert!(
+            checkpoint
+                .load_chapter(0, &volume.chapter_list[0])
+                .is_none()
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::Config;
+    #[tokio::test]
+    async fn test_update_volume() {
+        let temp = tempfile::tempdir().unwrap();
+        let output = temp.path();
+        let mut downloader = replay_downloader(output).await;
+
+        // 上次下载时只有第一章
+        let volume = downloader.volume_infos[0].clone();
+        downloader.volume_infos[0].chapter_list.truncate(1);
+        downloader.volume_infos[0].chapter_path_list.truncate(1);
+        downloader.download(1..=1).await.unwrap();
+        let first = read_chapter(output, "OEBPS/Text/001.xhtml");
+        assert!(first.contains("epub_download:source"));
+        let path = output.join("测试小说").join("测试小说-第一卷.epub");
+        assert_eq!(ExistingEpub::open(&path).unwrap().chapters.len(), 1);
+
+        downloader.volume_infos[0] = volume;
+        downloader.update(1..=1).await.unwrap();
+        let epub = ExistingEpub::open(&path).unwrap();
+        assert_eq!(
+            epub.chapters
+                .iter()
+                .map(|chapter| chapter.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["第一章 开始", "第二章 结束"]
+        );
+        // 第一章原样保留，插图编号不变
+        assert_eq!(read_chapter(output, "OEBPS/Text/001.xhtml"), first);
+        assert_eq!(epub.images.len(), 2);
+    }
 
-    #[ignore]
     #[tokio::test]
-    async fn test_get_chapterlog_version() {
-        let config = Config::default();
-        let client = BiliClient::new(
-            config.base_url.as_str(),
-            config.cookie.as_str(),
-            config.user_agent.as_str(),
-            &config.headers,
-            config.convert_simple_chinese,
-            config.debug,
-        )
+    async fn test_update_skips_empty_chapters() {
+        let temp = tempfile::tempdir().unwrap();
+        let output = temp.path();
+        let mut downloader = replay_downloader(output).await;
+
+        // 第二章的内容全部被过滤
+        let filter = ContentFilter::new(&FilterConfig {
+            rules: vec![FilterRule {
+                pattern: String::from("第二章的内容"),
+                regex: false,
+                scope: FilterScope::Paragraph,
+            }],
+            dry_run: false,
+        })
         .unwrap();
-        let html = client
-            .get("https://www.bilinovel.com/novel/1/108523.html")
-            .await
-            .unwrap();
-        let version = Downloader::get_chapterlog_version(&html).unwrap();
-        println!("version: {}", version);
+        let default_filter = std::mem::replace(&mut downloader.filter, filter);
+        downloader.download(1..=1).await.unwrap();
+        let path = output.join("测试小说").join("测试小说-第一卷.epub");
+        let epub = ExistingEpub::open(&path).unwrap();
+        assert_eq!(epub.chapters.len(), 1);
+        assert_eq!(epub.skipped, vec![String::from("/novel/9999/1002.html")]);
+
+        // 更新时不再下载第二章，否则不过滤时会收录
+        downloader.filter = default_filter;
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        downloader.update(1..=1).await.unwrap();
+        assert_eq!(ExistingEpub::open(&path).unwrap().chapters.len(), 1);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().modified().unwrap(),
+            modified
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_omnibus() {
+        let temp = tempfile::tempdir().unwrap();
+        let output = temp.path();
+        let mut downloader = replay_downloader(output).await;
+        let checkpoint = |volume: &str| {
+            Checkpoint::new(
+                &downloader.checkpoint_dir,
+                "9999",
+                &format!("/novel/9999/{}.html", volume),
+            )
+        };
+
+        // 第二卷的第三章没有记录，下载在第二卷中断
+        let second = downloader.volume_infos[1].clone();
+        downloader.volume_infos[1]
+            .chapter_list
+            .push(String::from("第四章 未完"));
+        downloader.volume_infos[1]
+            .chapter_path_list
+            .push(String::from("/novel/9999/2003.html"));
+        assert!(downloader.download_omnibus(1..=2).await.is_err());
+        // 每卷的章节保存在各自的断点中
+        assert!(checkpoint("vol_1").load_chapter(1, "第二章 结束").is_some());
+        assert!(checkpoint("vol_2").load_chapter(0, "插图").is_some());
+        assert!(checkpoint("vol_2").load_chapter(1, "第三章 继续").is_some());
+        assert!(checkpoint("vol_2").load_chapter(1, "第二章 结束").is_none());
+
+        downloader.volume_infos[1] = second;
+        downloader.download_omnibus(1..=2).await.unwrap();
+        assert!(checkpoint("vol_1").load_chapter(0, "第一章 开始").is_none());
+        assert!(checkpoint("vol_2").load_chapter(0, "插图").is_none());
+
+        let path = output.join("测试小说").join("测试小说-全2卷.epub");
+        let epub = ExistingEpub::open(&path).unwrap();
+        assert_eq!(
+            epub.chapters
+                .iter()
+                .map(|chapter| chapter.title.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "封面",
+                "第一章 开始",
+                "第二章 结束",
+                "封面",
+                "信息",
+                "彩页",
+                "第三章 继续"
+            ]
+        );
+        // 第二卷彩页的第一张图作为第二卷的封面，不再出现在彩页中
+        assert_eq!(epub.images.len(), 4);
+
+        let mut zip = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut text = String::new();
+            zip.by_name(name)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            text
+        };
+        let nav = read("OEBPS/Text/nav.xhtml");
+        assert_eq!(nav.matches("<ol>").count(), 3);
+        assert!(nav.find("第一卷").unwrap() < nav.find("第二卷").unwrap());
+        let cover = read("OEBPS/Text/004.xhtml");
+        assert!(cover.contains("../Images/002.png"));
+        let info = read("OEBPS/Text/005.xhtml");
+        assert!(info.contains("插画：测试画师"));
+        assert!(!info.contains("<img"));
+        let color = read("OEBPS/Text/006.xhtml");
+        assert!(color.contains("../Images/003.png"));
+        assert!(!color.contains("../Images/002.png"));
+
+        let opf = read("OEBPS/content.opf");
+        assert!(opf.contains(r#"<meta name="calibre:series" content="测试小说"/>"#));
+        assert!(!opf.contains("calibre:series_index"));
+        assert!(opf.contains("/novel/9999/omnibus_1-2.html"));
     }
 }
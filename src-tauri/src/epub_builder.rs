@@ -8,12 +8,18 @@ use std::{
 use chrono::Utc;
 use zip::{CompressionMethod, write::SimpleFileOptions};
 
-use crate::{bail, error::Result};
+use crate::{
+    bail,
+    error::Result,
+    model::{Content, Inline},
+};
 
 #[derive(Default, Debug)]
 pub struct Metadata {
     pub title: String,
     pub creator: Option<String>,
+    /// 插画师
+    pub illustrator: Option<String>,
     pub publisher: Option<String>,
     pub description: Option<String>,
     pub series: Option<String>,
@@ -21,11 +27,16 @@ pub struct Metadata {
     pub language: Option<String>,
     pub index: Option<usize>,
     pub identifier: Option<String>,
+    /// 最后更新日期
+    pub date: Option<String>,
+    pub word_count: Option<u64>,
+    pub chapter_count: Option<u32>,
 }
 
 pub struct MetadataConfig<'a> {
     pub title: &'a str,
     pub creator: Option<&'a str>,
+    pub illustrator: Option<&'a str>,
     pub publisher: Option<&'a str>,
     pub description: Option<&'a str>,
     pub series: Option<&'a str>,
@@ -33,6 +44,9 @@ pub struct MetadataConfig<'a> {
     pub language: Option<&'a str>,
     pub index: Option<usize>,
     pub identifier: Option<&'a str>,
+    pub date: Option<&'a str>,
+    pub word_count: Option<u64>,
+    pub chapter_count: Option<u32>,
 }
 
 impl<'a> From<MetadataConfig<'a>> for Metadata {
@@ -42,6 +56,9 @@ impl<'a> From<MetadataConfig<'a>> for Metadata {
             creator: config
                 .creator
                 .map(|c| remove_invalid_xml_chars(&escape_epub_text(c))),
+            illustrator: config
+                .illustrator
+                .map(|i| remove_invalid_xml_chars(&escape_epub_text(i))),
             publisher: config
                 .publisher
                 .map(|p| remove_invalid_xml_chars(&escape_epub_text(p))),
@@ -59,6 +76,11 @@ impl<'a> From<MetadataConfig<'a>> for Metadata {
             identifier: config
                 .identifier
                 .map(|i| remove_invalid_xml_chars(&escape_epub_text(i))),
+            date: config
+                .date
+                .map(|d| remove_invalid_xml_chars(&escape_epub_text(d))),
+            word_count: config.word_count,
+            chapter_count: config.chapter_count,
         }
     }
 }
@@ -71,13 +93,26 @@ pub enum Body {
 }
 
 pub enum ContentBlock {
-    /// html中的标签
-    Tag(String),
-    Text(String),
+    /// 段落、标题等正文内容
+    Content(Content),
     /// images中的索引
     Image(usize),
+    /// 已经序列化的xhtml，例如更新时保留的旧章节
+    Html(String),
 }
 
+/// 目录中的一级标题，例如合集中的一卷
+pub struct Section {
+    pub title: String,
+    /// 第一章的索引，直到下一个`Section`之前的章节都属于该标题
+    pub start: usize,
+}
+
+/// 章节xhtml中记录目录链接的meta
+pub const SOURCE_META: &str = "epub_download:source";
+/// opf中记录因内容为空没有收录的章节链接的meta，每章一个
+pub const SKIPPED_META: &str = "epub_download:skipped";
+
 pub struct EpubBuilder {
     metadata: Metadata,
     chapters: Body,
@@ -86,6 +121,12 @@ pub struct EpubBuilder {
     image_exts: Vec<String>,
     image_alts: Vec<String>,
     add_catalog: bool,
+    /// 章节在目录中的链接，更新时用于判断章节是否变化
+    chapter_sources: Vec<String>,
+    /// 内容为空没有收录的章节在目录中的链接，更新时不再下载
+    skipped_sources: Vec<String>,
+    /// 为空时目录只有一级
+    sections: Vec<Section>,
 }
 
 impl EpubBuilder {
@@ -98,26 +139,9 @@ impl EpubBuilder {
         image_alts: Vec<String>,
         add_catalog: bool,
     ) -> Self {
+        // 内容块在序列化时转义
         let chapters = match chapters {
-            Body::Blocks(blocks) => Body::Blocks(
-                blocks
-                    .into_iter()
-                    .map(|chapter| {
-                        chapter
-                            .into_iter()
-                            .map(|block| match block {
-                                ContentBlock::Text(text) => ContentBlock::Text(
-                                    remove_invalid_xml_chars(&escape_epub_text(&text)),
-                                ),
-                                ContentBlock::Image(_) => block,
-                                ContentBlock::Tag(tag) => {
-                                    ContentBlock::Tag(remove_invalid_xml_chars(&tag))
-                                }
-                            })
-                            .collect()
-                    })
-                    .collect(),
-            ),
+            Body::Blocks(blocks) => Body::Blocks(blocks),
             Body::Html(html) => Body::Html(
                 html.into_iter()
                     .map(|t| remove_invalid_xml_chars(&t))
@@ -136,9 +160,54 @@ impl EpubBuilder {
             image_exts,
             image_alts,
             add_catalog,
+            chapter_sources: Vec::new(),
+            skipped_sources: Vec::new(),
+            sections: Vec::new(),
         }
     }
 
+    pub fn with_sections(mut self, sections: Vec<Section>) -> Self {
+        self.sections = sections
+            .into_iter()
+            .map(|section| Section {
+                title: remove_invalid_xml_chars(&escape_epub_text(&section.title)),
+                start: section.start,
+            })
+            .collect();
+        self
+    }
+
+    /// 每个一级标题包含的章节范围
+    fn section_ranges(&self) -> Vec<(&str, std::ops::Range<usize>)> {
+        self.sections
+            .iter()
+            .enumerate()
+            .map(|(i, section)| {
+                let end = self
+                    .sections
+                    .get(i + 1)
+                    .map_or(self.chapter_titles.len(), |next| next.start);
+                (section.title.as_str(), section.start..end)
+            })
+            .collect()
+    }
+
+    pub fn with_chapter_sources(mut self, chapter_sources: Vec<String>) -> Self {
+        self.chapter_sources = chapter_sources
+            .into_iter()
+            .map(|s| escape_attr(&s))
+            .collect();
+        self
+    }
+
+    pub fn with_skipped_sources(mut self, skipped_sources: Vec<String>) -> Self {
+        self.skipped_sources = skipped_sources
+            .into_iter()
+            .map(|s| escape_attr(&s))
+            .collect();
+        self
+    }
+
     pub fn build_epub(&self) -> HashMap<String, Vec<u8>> {
         let mut epub = HashMap::new();
         // mimetype需要是第一个文件
@@ -169,9 +238,13 @@ impl EpubBuilder {
         for (i, _) in html.iter().enumerate() {
             epub.insert(
                 format!("OEBPS/Text/{}.xhtml", self.num_fill(i + 1)),
-                self.build_xhtml(&self.chapter_titles[i], &html[i])
-                    .as_bytes()
-                    .to_vec(),
+                self.build_xhtml(
+                    &self.chapter_titles[i],
+                    self.chapter_sources.get(i).map(String::as_str),
+                    &html[i],
+                )
+                .as_bytes()
+                .to_vec(),
             );
         }
         epub.insert(
@@ -195,26 +268,22 @@ impl EpubBuilder {
         chapters
             .iter()
             .map(|chapter| {
-                chapter
+                let mut notes = Footnotes::default();
+                let mut lines = chapter
                     .iter()
                     .map(|block| match block {
-                        ContentBlock::Tag(tag) => tag.to_string(),
-                        ContentBlock::Text(text) => {
-                            if text.is_empty() {
-                                String::from("<br/>")
-                            } else {
-                                format!("<p>{}</p>", text)
-                            }
-                        }
+                        ContentBlock::Content(content) => content_to_html(content, &mut notes),
                         ContentBlock::Image(image) => format!(
                             "<img src=\"../Images/{}.{}\" alt=\"{}\" />",
                             self.num_fill(*image),
                             self.image_exts[*image],
                             self.image_alts[*image]
                         ),
+                        ContentBlock::Html(html) => html.clone(),
                     })
-                    .collect::<Vec<String>>()
-                    .join("\n    ")
+                    .collect::<Vec<String>>();
+                lines.extend(notes.to_html());
+                lines.join("\n    ")
             })
             .collect()
     }
@@ -258,7 +327,7 @@ impl EpubBuilder {
  "http://www.daisy.org/z3986/2005/ncx-2005-1.dtd">
 <ncx version="2005-1" xmlns="http://www.daisy.org/z3986/2005/ncx/">
   <head>
-    <meta name="dtb:depth" content="1" />
+    <meta name="dtb:depth" content="{}" />
     <meta name="dtb:totalPageCount" content="0" />
     <meta name="dtb:maxPageNumber" content="0" />
   </head>
@@ -269,15 +338,15 @@ impl EpubBuilder {
     {}
   </navMap>
 </ncx>"#,
+            if self.sections.is_empty() { 1 } else { 2 },
             self.metadata.title,
             self.get_nav_xml()
         )
     }
 
     fn get_nav_xml(&self) -> String {
-        let mut nav_map = Vec::new();
-        for i in 0..self.chapter_titles.len() {
-            nav_map.push(format!(
+        let nav_point = |i: usize| {
+            format!(
                 r#"<navPoint id="navPoint-{}" playOrder="{}">
       <navLabel>
         <text>{}</text>
@@ -288,6 +357,31 @@ impl EpubBuilder {
                 i + 1,
                 self.chapter_titles[i],
                 self.num_fill(i + 1),
+            )
+        };
+        if self.sections.is_empty() {
+            return (0..self.chapter_titles.len())
+                .map(nav_point)
+                .collect::<Vec<_>>()
+                .join("\n    ");
+        }
+
+        let mut nav_map = Vec::new();
+        for (i, (title, range)) in self.section_ranges().into_iter().enumerate() {
+            // 与第一章指向同一文件，playOrder 也相同
+            nav_map.push(format!(
+                r#"<navPoint id="navPoint-section-{}" playOrder="{}">
+      <navLabel>
+        <text>{}</text>
+      </navLabel>
+      <content src="Text/{}.xhtml" />
+    {}
+    </navPoint>"#,
+                i + 1,
+                range.start + 1,
+                title,
+                self.num_fill(range.start + 1),
+                range.map(nav_point).collect::<Vec<_>>().join("\n    "),
             ));
         }
         nav_map.join("\n    ")
@@ -384,6 +478,12 @@ impl EpubBuilder {
         if let Some(author) = &self.metadata.creator {
             metadata.push(format!("<dc:creator>{}</dc:creator>", author));
         }
+        if let Some(illustrator) = &self.metadata.illustrator {
+            metadata.push(format!(
+                "<dc:contributor id=\"illustrator\">{}</dc:contributor>",
+                illustrator
+            ));
+        }
         if let Some(publisher) = &self.metadata.publisher {
             metadata.push(format!("<dc:publisher>{}</dc:publisher>", publisher));
         }
@@ -399,6 +499,9 @@ impl EpubBuilder {
                 identifier
             ));
         }
+        if let Some(date) = &self.metadata.date {
+            metadata.push(format!("<dc:date>{}</dc:date>", date));
+        }
         metadata.push(
             self.metadata
                 .subject
@@ -411,6 +514,11 @@ impl EpubBuilder {
             "<meta property=\"dcterms:modified\">{}</meta>",
             get_time()
         ));
+        if self.metadata.illustrator.is_some() {
+            metadata.push(String::from(
+                "<meta refines=\"#illustrator\" property=\"role\" scheme=\"marc:relators\">ill</meta>",
+            ));
+        }
 
         metadata.push(format!(
             "<meta name=\"cover\" content=\"x000.{}\"/>",
@@ -428,6 +536,24 @@ impl EpubBuilder {
                 index
             ));
         }
+        if let Some(word_count) = &self.metadata.word_count {
+            metadata.push(format!(
+                "<meta name=\"epub_download:word_count\" content=\"{}\"/>",
+                word_count
+            ));
+        }
+        if let Some(chapter_count) = &self.metadata.chapter_count {
+            metadata.push(format!(
+                "<meta name=\"epub_download:chapter_count\" content=\"{}\"/>",
+                chapter_count
+            ));
+        }
+        for source in &self.skipped_sources {
+            metadata.push(format!(
+                "<meta name=\"{}\" content=\"{}\"/>",
+                SKIPPED_META, source
+            ));
+        }
 
         metadata.join("\n    ")
     }
@@ -442,26 +568,34 @@ impl EpubBuilder {
             .to_string()
     }
 
-    fn build_xhtml(&self, title: &str, body: &str) -> String {
-        let title_tag = if title != "彩页" {
+    fn build_xhtml(&self, title: &str, source: Option<&str>, body: &str) -> String {
+        let title_tag = if title != "彩页" && title != "封面" {
             format!("<h1>{}</h1>\n    ", title)
         } else {
             String::new()
         };
+        let source_meta = source
+            .map(|source| {
+                format!(
+                    "\n    <meta name=\"{}\" content=\"{}\"/>",
+                    SOURCE_META, source
+                )
+            })
+            .unwrap_or_default();
         format!(
             r#"<?xml version="1.0" encoding="utf-8"?>
 <!DOCTYPE html>
 
 <html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
   <head>
-    <title>{}</title>
+    <title>{}</title>{}
     <style type="text/css">p{{text-indent:2em;}}</style>
   </head>
   <body>
     {}{}
   </body>
 </html>"#,
-            title, title_tag, body
+            title, source_meta, title_tag, body
         )
     }
 
@@ -497,15 +631,28 @@ impl EpubBuilder {
         } else {
             String::new()
         };
-        let mut nav_map = Vec::new();
-
-        for i in 0..self.chapter_titles.len() {
-            nav_map.push(format!(
+        let item = |i: usize| {
+            format!(
                 "<li><a href=\"{}.xhtml\">{}</a></li>",
                 self.num_fill(i + 1),
                 self.chapter_titles[i]
-            ));
-        }
+            )
+        };
+        let nav_map: Vec<String> = if self.sections.is_empty() {
+            (0..self.chapter_titles.len()).map(item).collect()
+        } else {
+            self.section_ranges()
+                .into_iter()
+                .map(|(title, range)| {
+                    format!(
+                        "<li><a href=\"{}.xhtml\">{}</a>\n        <ol>\n          {}\n        </ol>\n      </li>",
+                        self.num_fill(range.start + 1),
+                        title,
+                        range.map(item).collect::<Vec<_>>().join("\n          ")
+                    )
+                })
+                .collect()
+        };
 
         format!(
             r#"<?xml version="1.0" encoding="utf-8"?>
@@ -580,6 +727,82 @@ nav#toc ol li a:hover {
     }
 }
 
+/// 一章中的脚注，按出现顺序编号
+#[derive(Default)]
+pub struct Footnotes {
+    notes: Vec<String>,
+}
+
+impl Footnotes {
+    /// 添加脚注，返回正文中的引用
+    fn push(&mut self, html: String) -> String {
+        self.notes.push(html);
+        let n = self.notes.len();
+        format!(
+            "<a epub:type=\"noteref\" href=\"#note-{n}\" id=\"noteref-{n}\"><sup>[{n}]</sup></a>"
+        )
+    }
+
+    /// 放在章节末尾的脚注内容，阅读器会以弹窗显示
+    pub fn to_html(&self) -> Vec<String> {
+        self.notes
+            .iter()
+            .enumerate()
+            .map(|(i, note)| {
+                let n = i + 1;
+                format!(
+                    "<aside epub:type=\"footnote\" id=\"note-{n}\"><p><a href=\"#noteref-{n}\">{n}</a>. {note}</p></aside>"
+                )
+            })
+            .collect()
+    }
+}
+
+/// 把正文内容序列化为xhtml，脚注收集到`notes`中
+pub fn content_to_html(content: &Content, notes: &mut Footnotes) -> String {
+    match content {
+        Content::Paragraph(inlines) => format!("<p>{}</p>", inlines_to_html(inlines, notes)),
+        Content::Block(inlines) => format!("<div>{}</div>", inlines_to_html(inlines, notes)),
+        Content::Heading { level, children } => {
+            let level = (*level).clamp(1, 6);
+            format!("<h{0}>{1}</h{0}>", level, inlines_to_html(children, notes))
+        }
+        Content::Image(url) => format!("<img src=\"{}\" alt=\"\" />", escape_attr(url)),
+        Content::Separator => String::from("<hr/>"),
+        Content::LineBreak => String::from("<br/>"),
+    }
+}
+
+fn inlines_to_html(inlines: &[Inline], notes: &mut Footnotes) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => remove_invalid_xml_chars(&escape_epub_text(text)),
+            Inline::Bold(children) => format!("<b>{}</b>", inlines_to_html(children, notes)),
+            Inline::Italic(children) => format!("<i>{}</i>", inlines_to_html(children, notes)),
+            Inline::Ruby { base, annotation } => format!(
+                "<ruby>{}<rt>{}</rt></ruby>",
+                remove_invalid_xml_chars(&escape_epub_text(base)),
+                remove_invalid_xml_chars(&escape_epub_text(annotation))
+            ),
+            Inline::Link { href, children } => format!(
+                "<a href=\"{}\">{}</a>",
+                escape_attr(href),
+                inlines_to_html(children, notes)
+            ),
+            Inline::LineBreak => String::from("<br/>"),
+            Inline::Note(children) => {
+                let html = inlines_to_html(children, notes);
+                notes.push(html)
+            }
+        })
+        .collect()
+}
+
+fn escape_attr(input: &str) -> String {
+    remove_invalid_xml_chars(&escape_epub_text(input).replace('"', "&quot;"))
+}
+
 fn add_file(epub: &mut HashMap<String, Vec<u8>>, file: (String, Vec<u8>)) {
     epub.insert(file.0, file.1);
 }
@@ -600,9 +823,78 @@ pub fn escape_epub_text(input: &str) -> String {
         .replace(">", "&gt;")
 }
 
+pub fn unescape_epub_text(input: &str) -> String {
+    input
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
 pub fn remove_invalid_xml_chars(input: &str) -> String {
     input
         .chars()
         .filter(|&c| matches!(c as u32, 0x9 | 0xA | 0xD | 0x20..=0xD7FF | 0xE000..=0xFFFD | 0x10000..=0x10FFFF))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_to_html() {
+        let content = Content::Paragraph(vec![
+            Inline::Text(String::from("a < b & c")),
+            Inline::Bold(vec![Inline::Italic(vec![Inline::Text(String::from(
+                "粗斜体",
+            ))])]),
+            Inline::Ruby {
+                base: String::from("漢字"),
+                annotation: String::from("かんじ"),
+            },
+            Inline::LineBreak,
+            Inline::Link {
+                href: String::from("/a?b=1&c=\"2\""),
+                children: vec![Inline::Text(String::from("链接"))],
+            },
+        ]);
+        let mut notes = Footnotes::default();
+        assert_eq!(
+            content_to_html(&content, &mut notes),
+            "<p>a &lt; b &amp; c<b><i>粗斜体</i></b><ruby>漢字<rt>かんじ</rt></ruby><br/>\
+             <a href=\"/a?b=1&amp;c=&quot;2&quot;\">链接</a></p>"
+        );
+        assert_eq!(
+            content_to_html(
+                &Content::Heading {
+                    level: 9,
+                    children: vec![Inline::Text(String::from("标题"))],
+                },
+                &mut notes
+            ),
+            "<h6>标题</h6>"
+        );
+        assert!(notes.to_html().is_empty());
+    }
+
+    #[test]
+    fn test_footnote_to_html() {
+        let mut notes = Footnotes::default();
+        let content = Content::Paragraph(vec![
+            Inline::Text(String::from("前辈")),
+            Inline::Note(vec![Inline::Text(String::from("原文为「先輩」"))]),
+            Inline::Text(String::from("。")),
+        ]);
+        assert_eq!(
+            content_to_html(&content, &mut notes),
+            "<p>前辈<a epub:type=\"noteref\" href=\"#note-1\" id=\"noteref-1\"><sup>[1]</sup></a>。</p>"
+        );
+        assert_eq!(
+            notes.to_html(),
+            vec![
+                "<aside epub:type=\"footnote\" id=\"note-1\"><p><a href=\"#noteref-1\">1</a>. 原文为「先輩」</p></aside>"
+            ]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::{collections::BTreeMap, fs::File, io::Read, path::Path};
+
+use quick_xml::de::from_str;
+use regex::Regex;
+use zip::ZipArchive;
+
+use crate::{
+    epub_builder::{SKIPPED_META, SOURCE_META, unescape_epub_text},
+    error::Result,
+    manage::Package,
+};
+
+/// 已下载的epub中的一章
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpubChapter {
+    pub title: String,
+    /// 章节在目录中的链接，旧版本生成的epub中没有
+    pub source: Option<String>,
+    /// `<body>`中除标题外的xhtml
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpubImage {
+    pub ext: String,
+    pub data: Vec<u8>,
+}
+
+/// 读取由 [`crate::epub_builder::EpubBuilder`] 生成的epub，用于增量更新
+#[derive(Debug)]
+pub struct ExistingEpub {
+    pub identifier: Option<String>,
+    pub chapters: Vec<EpubChapter>,
+    /// 按编号排列，第一张为封面
+    pub images: Vec<EpubImage>,
+    /// 下载时内容为空没有收录的章节链接
+    pub skipped: Vec<String>,
+}
+
+impl ExistingEpub {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut zip = ZipArchive::new(File::open(path)?)?;
+
+        let opf = read_string(&mut zip, "OEBPS/content.opf")?;
+        let package: Package = from_str(&opf)?;
+
+        let mut chapters = Vec::new();
+        for i in 1.. {
+            let Ok(xhtml) = read_string(&mut zip, &format!("OEBPS/Text/{:03}.xhtml", i)) else {
+                break;
+            };
+            chapters.push(parse_chapter(&xhtml));
+        }
+
+        let image_names: BTreeMap<usize, String> = zip
+            .file_names()
+            .filter_map(|name| {
+                let (no, ext) = name.strip_prefix("OEBPS/Images/")?.split_once('.')?;
+                Some((no.parse().ok()?, ext.to_string()))
+            })
+            .collect();
+        let mut images = Vec::new();
+        for (no, ext) in image_names {
+            let mut data = Vec::new();
+            zip.by_name(&format!("OEBPS/Images/{:03}.{}", no, ext))?
+                .read_to_end(&mut data)?;
+            images.push(EpubImage { ext, data });
+        }
+
+        let skipped = package
+            .metadata
+            .meta
+            .into_iter()
+            .filter(|meta| meta.name.as_deref() == Some(SKIPPED_META))
+            .filter_map(|meta| meta.content)
+            .collect();
+
+        Ok(Self {
+            identifier: package.metadata.identifier,
+            chapters,
+            images,
+            skipped,
+        })
+    }
+}
+
+fn read_string(zip: &mut ZipArchive<File>, name: &str) -> Result<String> {
+    let mut content = String::new();
+    zip.by_name(name)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+fn parse_chapter(xhtml: &str) -> EpubChapter {
+    let between = |start: &str, end: &str| {
+        let from = xhtml.find(start)? + start.len();
+        let to = xhtml.rfind(end)?;
+        xhtml.get(from..to)
+    };
+    let raw_title = between("<title>", "</title>").unwrap_or_default();
+    let source_re = Regex::new(&format!(
+        r#"<meta name="{}" content="([^"]*)"/>"#,
+        SOURCE_META
+    ))
+    .unwrap();
+    let source = source_re
+        .captures(xhtml)
+        .map(|captures| unescape_epub_text(&captures[1]));
+
+    let body = between("<body>", "</body>").unwrap_or_default().trim();
+    let heading = format!("<h1>{}</h1>", raw_title);
+    let body = body.strip_prefix(&heading).unwrap_or(body).trim_start();
+
+    EpubChapter {
+        title: unescape_epub_text(raw_title),
+        source,
+        body: body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        epub_builder::{Body, ContentBlock, EpubBuilder, Metadata},
+        model::Content,
+    };
+
+    #[test]
+    fn test_read_built_epub() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("reader.epub");
+        let metadata = Metadata {
+            title: String::from("测试"),
+            identifier: Some(String::from("/novel/1/vol_2.html")),
+            ..Metadata::default()
+        };
+        EpubBuilder::new(
+            metadata,
+            Body::Blocks(vec![
+                vec![ContentBlock::Image(1)],
+                vec![
+                    ContentBlock::Content(Content::paragraph("第一段 & 第二段")),
+                    ContentBlock::Content(Content::paragraph("第三段")),
+                ],
+            ]),
+            vec![String::from("彩页"), String::from("<序章>")],
+            vec![b"cover".to_vec(), b"image".to_vec()],
+            vec![String::from("jpg"), String::from("png")],
+            vec![
+                String::from("cover"),
+                String::from("https://img.example.com/1.png"),
+            ],
+            false,
+        )
+        .with_chapter_sources(vec![
+            String::from("/novel/1/3.html"),
+            String::from("/novel/1/4.html?a=1&b=2"),
+        ])
+        .save_file(&path)
+        .unwrap();
+
+        let epub = ExistingEpub::open(&path).unwrap();
+        assert_eq!(epub.identifier.as_deref(), Some("/novel/1/vol_2.html"));
+        assert_eq!(
+            epub.chapters,
+            vec![
+                EpubChapter {
+                    title: String::from("彩页"),
+                    source: Some(String::from("/novel/1/3.html")),
+                    body: String::from(
+                        "<img src=\"../Images/001.png\" alt=\"https://img.example.com/1.png\" />"
+                    ),
+                },
+                EpubChapter {
+                    title: String::from("<序章>"),
+                    source: Some(String::from("/novel/1/4.html?a=1&b=2")),
+                    body: String::from("<p>第一段 &amp; 第二段</p>\n    <p>第三段</p>"),
+                },
+            ]
+        );
+        assert_eq!(
+            epub.images,
+            vec![
+                EpubImage {
+                    ext: String::from("jpg"),
+                    data: b"cover".to_vec(),
+                },
+                EpubImage {
+                    ext: String::from("png"),
+                    data: b"image".to_vec(),
+                },
+            ]
+        );
+    }
+}
@@ -1,17 +1,144 @@
+use std::fmt;
+
+use color_eyre::eyre::Report;
+
+/// 站点返回的失败情况
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub enum SiteError {
+    /// 该书内容已删除
+    Deleted,
+    /// 章节或小说未通过审核
+    NotReviewed,
+    /// 小说不存在
+    NotFound,
+    /// 触发 Cloudflare 等反爬限制
+    Blocked { message: String },
+    /// 请求被重定向到其他页面
+    Redirected { from: String, to: String },
+    /// 网络错误，重试后仍然失败
+    Network { message: String },
+    /// 页面内容解析失败
+    Parse { message: String },
+    /// 离线模式下缓存中没有该页面或图片
+    CacheMiss { url: String },
+}
+
+impl SiteError {
+    pub fn blocked(message: impl Into<String>) -> Self {
+        Self::Blocked {
+            message: message.into(),
+        }
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        Self::Network {
+            message: message.into(),
+        }
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::Parse {
+            message: message.into(),
+        }
+    }
+
+    /// 从错误链中找出站点错误
+    pub fn find(err: &Report) -> Option<&SiteError> {
+        err.chain().find_map(|e| e.downcast_ref::<SiteError>())
+    }
+
+    /// 重试也无法恢复的错误
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            Self::Deleted
+                | Self::NotReviewed
+                | Self::NotFound
+                | Self::Redirected { .. }
+                | Self::CacheMiss { .. }
+        )
+    }
+
+    /// 命令行退出码
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Deleted => 10,
+            Self::NotReviewed => 11,
+            Self::NotFound => 12,
+            Self::Blocked { .. } => 13,
+            Self::Redirected { .. } => 14,
+            Self::Network { .. } => 15,
+            Self::Parse { .. } => 16,
+            Self::CacheMiss { .. } => 17,
+        }
+    }
+
+    /// 给用户的处理建议
+    pub fn remedy(&self) -> &'static str {
+        match self {
+            Self::Deleted => "该书已被站点删除，无法下载",
+            Self::NotReviewed => "该书或章节尚未通过站点审核，请等待审核通过后再下载",
+            Self::NotFound => "请检查书籍id是否正确",
+            Self::Blocked { .. } => {
+                "请稍后再试，或在浏览器中通过验证后导入cookie，也可以降低请求频率或使用代理"
+            }
+            Self::Redirected { .. } => "请检查站点地址是否正确，或更新cookie后重新登录",
+            Self::Network { .. } => "请检查网络连接或代理设置",
+            Self::Parse { .. } => "站点页面结构可能已变化，请更新到最新版本",
+            Self::CacheMiss { .. } => "请关闭离线模式，或先联网下载一次以写入缓存",
+        }
+    }
+}
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deleted => write!(f, "该书内容已删除"),
+            Self::NotReviewed => write!(f, "该书内容审核未通过"),
+            Self::NotFound => write!(f, "该小说不存在"),
+            Self::Blocked { message } => write!(f, "访问被站点限制: {}", message),
+            Self::Redirected { from, to } => write!(f, "url重定向: {} -> {}", from, to),
+            Self::Network { message } => write!(f, "网络错误: {}", message),
+            Self::Parse { message } => write!(f, "解析失败: {}", message),
+            Self::CacheMiss { url } => write!(f, "离线模式下缓存中没有: {}", url),
+        }
+    }
+}
+
+impl std::error::Error for SiteError {}
+
+/// 命令行退出码，站点错误使用各自的退出码，其他错误为1
+pub fn exit_code(err: &Report) -> i32 {
+    SiteError::find(err).map_or(1, SiteError::exit_code)
+}
+
 #[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "gui", derive(specta::Type))]
-pub struct CommandError(pub String);
+pub struct CommandError {
+    pub message: String,
+    pub site_error: Option<SiteError>,
+    pub remedy: Option<String>,
+}
 
 impl<E> From<E> for CommandError
 where
-    E: Into<color_eyre::eyre::Error>,
+    E: Into<Report>,
 {
     fn from(err: E) -> Self {
-        Self(format!("{:#}", err.into()))
+        let err = err.into();
+        let site_error = SiteError::find(&err).cloned();
+        Self {
+            message: format!("{:#}", err),
+            remedy: site_error.as_ref().map(|e| e.remedy().to_string()),
+            site_error,
+        }
     }
 }
 
-pub type Result<T, E = color_eyre::eyre::Error> = color_eyre::eyre::Result<T, E>;
+pub type Result<T, E = Report> = color_eyre::eyre::Result<T, E>;
 
 #[macro_export]
 macro_rules! bail {
@@ -26,3 +153,26 @@ macro_rules! err {
         ::color_eyre::eyre::eyre!($($arg)*)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::WrapErr;
+
+    use super::*;
+
+    #[test]
+    fn test_find_site_error() {
+        let err: Result<()> = Err(SiteError::NotFound.into());
+        let err = err.wrap_err("获取书籍信息失败").unwrap_err();
+        assert_eq!(SiteError::find(&err), Some(&SiteError::NotFound));
+        assert_eq!(exit_code(&err), 12);
+
+        let command_error = CommandError::from(err);
+        assert_eq!(command_error.site_error, Some(SiteError::NotFound));
+        assert!(command_error.remedy.is_some());
+
+        let err = crate::err!("其他错误");
+        assert_eq!(exit_code(&err), 1);
+        assert!(CommandError::from(err).site_error.is_none());
+    }
+}
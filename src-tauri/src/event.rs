@@ -5,8 +5,13 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter, Listener};
+use tauri_specta::Event;
 
-use crate::{bail, error::Result};
+use crate::{
+    bail,
+    error::Result,
+    progress::{DownloadEvent, EventSink},
+};
 
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -26,8 +31,17 @@ pub fn message(app_handle: &AppHandle, msg: &str) {
     app_handle.emit("message", msg).unwrap();
 }
 
-pub fn print(app_handle: &AppHandle, msg: &str) {
-    app_handle.emit("print", msg).unwrap();
+/// 把进度事件发送给前端
+pub struct TauriSink<'a>(pub &'a AppHandle);
+
+impl EventSink for TauriSink<'_> {
+    fn event(&self, event: &DownloadEvent) {
+        let _ = event.emit(self.0);
+    }
+
+    fn message(&self, msg: &str) {
+        message(self.0, msg);
+    }
 }
 
 pub fn html(app_handle: &AppHandle, html: &str) -> Result<String> {
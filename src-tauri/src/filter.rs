@@ -0,0 +1,248 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{bail, error::Result, model::Content};
+
+/// 规则的匹配范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub enum FilterScope {
+    /// 逐段匹配，删除匹配的段落
+    #[default]
+    Paragraph,
+    /// 匹配整章文本（段落之间以换行连接），可以匹配跨越多段的内容，删除涉及的所有段落
+    Chapter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct FilterRule {
+    pub pattern: String,
+    /// 为 false 时按普通文本匹配
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub scope: FilterScope,
+}
+
+impl FilterRule {
+    fn literal(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            regex: false,
+            scope: FilterScope::Paragraph,
+        }
+    }
+
+    fn regex(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            regex: true,
+            scope: FilterScope::Paragraph,
+        }
+    }
+}
+
+/// 删除正文中的广告、水印等段落
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct FilterConfig {
+    #[serde(default = "default_rules")]
+    pub rules: Vec<FilterRule>,
+    /// 只报告会被删除的段落，不修改内容
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            rules: default_rules(),
+            dry_run: false,
+        }
+    }
+}
+
+fn default_rules() -> Vec<FilterRule> {
+    vec![
+        FilterRule::literal("本文来自"),
+        FilterRule::literal("最新章节请到"),
+        FilterRule::literal("请记住本站域名"),
+        FilterRule::literal("手机用户请浏览"),
+        FilterRule::regex(r"^(?:https?://)?(?:www|m)\.[\w-]+\.(?:com|net|org|cc)\S*$"),
+    ]
+}
+
+/// 编译后的过滤规则
+#[derive(Debug, Clone, Default)]
+pub struct ContentFilter {
+    rules: Vec<(Regex, FilterScope)>,
+    dry_run: bool,
+}
+
+impl ContentFilter {
+    pub fn new(config: &FilterConfig) -> Result<Self> {
+        let mut rules = Vec::new();
+        for rule in &config.rules {
+            let pattern = if rule.regex {
+                rule.pattern.clone()
+            } else {
+                regex::escape(&rule.pattern)
+            };
+            match Regex::new(&pattern) {
+                Ok(re) => rules.push((re, rule.scope)),
+                Err(err) => bail!("过滤规则 {} 无效: {}", rule.pattern, err),
+            }
+        }
+        Ok(Self {
+            rules,
+            dry_run: config.dry_run,
+        })
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// 过滤一章的内容，返回被删除（预览模式下为将被删除）的段落文本
+    pub fn apply(&self, chapter: &mut Vec<Content>) -> Vec<String> {
+        let matched = self.find(chapter);
+        let removed = matched.iter().map(|&i| chapter[i].text()).collect();
+        if !self.dry_run {
+            for i in matched.into_iter().rev() {
+                chapter.remove(i);
+            }
+        }
+        removed
+    }
+
+    /// 匹配的段落下标，按顺序排列
+    fn find(&self, chapter: &[Content]) -> Vec<usize> {
+        let texts: Vec<(usize, String)> = chapter
+            .iter()
+            .enumerate()
+            .filter(|(_, content)| content.is_text())
+            .map(|(i, content)| (i, content.text()))
+            .collect();
+
+        let mut matched = vec![false; chapter.len()];
+        let mut whole = None;
+        for (re, scope) in &self.rules {
+            match scope {
+                FilterScope::Paragraph => {
+                    for (i, text) in &texts {
+                        if re.is_match(text.trim()) {
+                            matched[*i] = true;
+                        }
+                    }
+                }
+                FilterScope::Chapter => {
+                    // 整章文本和每段在其中的范围
+                    let (text, ranges) = whole.get_or_insert_with(|| join_texts(&texts));
+                    for m in re.find_iter(text).filter(|m| !m.is_empty()) {
+                        for (i, start, end) in ranges.iter() {
+                            if *start < m.end() && m.start() < *end {
+                                matched[*i] = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        matched
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, matched)| matched.then_some(i))
+            .collect()
+    }
+}
+
+fn join_texts(texts: &[(usize, String)]) -> (String, Vec<(usize, usize, usize)>) {
+    let mut whole = String::new();
+    let mut ranges = Vec::new();
+    for (i, text) in texts {
+        if !whole.is_empty() {
+            whole.push('\n');
+        }
+        let start = whole.len();
+        whole.push_str(text);
+        ranges.push((*i, start, whole.len()));
+    }
+    (whole, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter() -> Vec<Content> {
+        vec![
+            Content::paragraph("第一段。"),
+            Content::paragraph("本文来自某某文库"),
+            Content::Image(String::from("https://example.com/1.jpg")),
+            Content::paragraph("www.example.com"),
+            Content::paragraph("—— 广告开始"),
+            Content::paragraph("广告内容"),
+            Content::paragraph("广告结束 ——"),
+            Content::paragraph("最后一段。"),
+        ]
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut config = FilterConfig::default();
+        config.rules.push(FilterRule {
+            pattern: String::from("广告开始[\\s\\S]*?广告结束"),
+            regex: true,
+            scope: FilterScope::Chapter,
+        });
+        let filter = ContentFilter::new(&config).unwrap();
+
+        let mut contents = chapter();
+        let removed = filter.apply(&mut contents);
+        assert_eq!(
+            removed,
+            vec![
+                "本文来自某某文库",
+                "www.example.com",
+                "—— 广告开始",
+                "广告内容",
+                "广告结束 ——"
+            ]
+        );
+        assert_eq!(
+            contents,
+            vec![
+                Content::paragraph("第一段。"),
+                Content::Image(String::from("https://example.com/1.jpg")),
+                Content::paragraph("最后一段。"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_dry_run() {
+        let config = FilterConfig {
+            dry_run: true,
+            ..FilterConfig::default()
+        };
+        let filter = ContentFilter::new(&config).unwrap();
+
+        let mut contents = chapter();
+        let removed = filter.apply(&mut contents);
+        assert_eq!(removed, vec!["本文来自某某文库", "www.example.com"]);
+        assert_eq!(contents, chapter());
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        let config = FilterConfig {
+            rules: vec![FilterRule::regex("(")],
+            dry_run: false,
+        };
+        assert!(ContentFilter::new(&config).is_err());
+    }
+}
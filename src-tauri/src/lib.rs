@@ -1,17 +1,31 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+pub mod cache;
+pub mod checkpoint;
 pub mod cli;
 pub mod client;
 pub mod config;
+pub mod cookie_jar;
 pub mod downloader;
 pub mod epub_builder;
+pub mod epub_reader;
 pub mod error;
+pub mod filter;
 pub mod manage;
 pub mod message;
 pub mod model;
 pub mod paragraph_restorer;
 pub mod parse;
+pub mod progress;
+pub mod proxy;
+pub mod queue;
+pub mod rate_limit;
+pub mod recorder;
+pub mod redirect;
+pub mod retry;
 pub mod runtime;
 pub mod secret;
+pub mod site_profile;
+pub mod source;
 pub mod utils;
 
 pub use cli::run_cli;
@@ -27,10 +41,10 @@ pub fn run() {
     use std::sync::Arc;
 
     use parking_lot::RwLock;
-    use tauri_specta::{Builder, collect_commands};
+    use tauri_specta::{Builder, collect_commands, collect_events};
     use tokio::sync::broadcast;
 
-    use crate::{command::*, config::Config};
+    use crate::{command::*, config::Config, progress::DownloadEvent};
 
     // 创建取消通道
     let (cancel_sender, _) = broadcast::channel::<()>(1);
@@ -39,7 +53,15 @@ pub fn run() {
     let builder = Builder::<tauri::Wry>::new()
         .commands(collect_commands![
             get_book_info,
+            search_books,
+            browse_books,
             download,
+            author_books,
+            download_author,
+            queue_list,
+            queue_add,
+            queue_remove,
+            queue_run,
             cancel_download,
             browser_url,
             save_config,
@@ -49,6 +71,7 @@ pub fn run() {
             get_books,
             create_index,
         ])
+        .events(collect_events![DownloadEvent])
         .error_handling(tauri_specta::ErrorHandlingMode::Throw);
 
     #[cfg(debug_assertions)] // <- Only export on non-release builds
@@ -62,6 +85,10 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(builder.invoke_handler())
+        .setup(move |app| {
+            builder.mount_events(app);
+            Ok(())
+        })
         .manage(RwLock::new(Config::load()))
         .manage(cancel_sender)
         .run(tauri::generate_context!())
@@ -1,7 +1,10 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use epub_download_lib::runtime::{RUN_MODE, RunMode};
+use epub_download_lib::{
+    error::exit_code,
+    runtime::{RUN_MODE, RunMode},
+};
 
 #[cfg(windows)]
 fn attach_console() {
@@ -21,7 +24,10 @@ async fn main() {
     if args.len() > 1 {
         // CLI mode
         *RUN_MODE.lock() = RunMode::Cli;
-        epub_download_lib::run_cli().await.unwrap();
+        if let Err(err) = epub_download_lib::run_cli().await {
+            eprintln!("{:?}", err);
+            std::process::exit(exit_code(&err));
+        }
     } else {
         *RUN_MODE.lock() = RunMode::Gui;
         #[cfg(feature = "gui")]
@@ -1,4 +1,8 @@
-use std::{collections::HashMap, fs::File, io::Read};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::Read,
+};
 
 use quick_xml::de::from_str;
 use serde::Deserialize;
@@ -6,12 +10,10 @@ use walkdir::WalkDir;
 use zip::ZipArchive;
 
 use crate::{
-    bail,
-    client::BiliClient,
-    err,
+    bail, err,
     error::Result,
     model::{Book, Volume},
-    parse::parse_last_update,
+    source::NovelSource,
 };
 
 #[derive(Debug, Deserialize)]
@@ -35,9 +37,11 @@ pub struct Metadata {
     pub language: Option<String>,
     #[serde(rename = "identifier", alias = "dc:identifier")]
     pub identifier: Option<String>,
-    #[serde(rename = "subject", alias = "dc:subject")]
+    #[serde(rename = "date", alias = "dc:date")]
+    pub date: Option<String>,
+    #[serde(rename = "subject", alias = "dc:subject", default)]
     pub subjects: Vec<String>,
-    #[serde(rename = "meta")]
+    #[serde(rename = "meta", default)]
     pub meta: Vec<Meta>,
 }
 
@@ -92,6 +96,8 @@ pub fn build_index(path: &str) -> Result<Vec<Book>> {
                 let mut updated_at = None;
                 let mut series = None;
                 let mut index = None;
+                let mut word_count = None;
+                let mut chapter_count = None;
                 for meta in package.metadata.meta {
                     if meta.property == Some("dcterms:modified".to_string()) {
                         updated_at = meta.value;
@@ -105,6 +111,14 @@ pub fn build_index(path: &str) -> Result<Vec<Book>> {
                         index = meta.content.and_then(|c| c.parse::<u32>().ok());
                         continue;
                     }
+                    if meta.name == Some("epub_download:word_count".to_string()) {
+                        word_count = meta.content.and_then(|c| c.parse::<u64>().ok());
+                        continue;
+                    }
+                    if meta.name == Some("epub_download:chapter_count".to_string()) {
+                        chapter_count = meta.content.and_then(|c| c.parse::<u32>().ok());
+                        continue;
+                    }
                 }
 
                 let volume = Volume {
@@ -118,6 +132,10 @@ pub fn build_index(path: &str) -> Result<Vec<Book>> {
                     volume_no: index.ok_or(err!("index is required"))?,
                     updated_at: updated_at.ok_or(err!("updated_at is required"))?,
                     path: entry.path().to_string_lossy().to_string(),
+                    description: package.metadata.description.clone(),
+                    last_update: package.metadata.date,
+                    word_count,
+                    chapter_count,
                 };
 
                 if books.contains_key(&id) {
@@ -171,12 +189,21 @@ pub fn get_books(index_path: &str) -> Result<Vec<Book>> {
     Ok(books)
 }
 
+/// 书库中已有的卷，以卷链接（不含站点地址）表示
+///
+/// 每次都扫描`output`目录，索引文件可能没有包含之后下载的卷。
+pub fn downloaded_volumes(output: &str) -> Result<HashSet<String>> {
+    Ok(build_index(output)?
+        .into_iter()
+        .flat_map(|book| book.volume_list)
+        .map(|volume| volume.url_vol)
+        .collect())
+}
+
 pub async fn get_last_update_by_with_volume(
-    client: &BiliClient,
+    source: &impl NovelSource,
     book_id: &str,
     volume_id: &str,
 ) -> Result<String> {
-    let html = client.get_volume(book_id, volume_id, None).await?;
-    let last_update = parse_last_update(&html).ok_or(err!("last_update is required"))?;
-    Ok(last_update)
+    source.fetch_last_update(book_id, volume_id, None).await
 }
@@ -1,4 +1,4 @@
-use crate::model::App;
+use crate::{model::App, progress::sink};
 
 pub fn send(_app: Option<&App>, msg: &str) {
     #[cfg(feature = "gui")]
@@ -8,16 +8,5 @@ pub fn send(_app: Option<&App>, msg: &str) {
         }
     }
 
-    println!("{}", msg);
-}
-
-pub fn print(_app: Option<&App>, msg: &str) {
-    #[cfg(feature = "gui")]
-    {
-        if let Some(app) = _app {
-            crate::event::print(app, msg);
-        }
-    }
-
-    print!("{}", msg);
+    sink().message(msg);
 }
@@ -1,6 +1,6 @@
 use serde::{self, Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "gui", derive(specta::Type))]
 pub struct BookInfo {
     pub title: Option<String>,
@@ -9,9 +9,22 @@ pub struct BookInfo {
     pub tags: Vec<String>,
     pub description: Option<String>,
     pub cover: Option<String>,
+    /// 连载中、已完结等
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub word_count: Option<u64>,
+    #[serde(default)]
+    pub last_update: Option<String>,
+    /// 插画师
+    #[serde(default)]
+    pub illustrator: Option<String>,
+    /// 别名
+    #[serde(default)]
+    pub aliases: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "gui", derive(specta::Type))]
 pub struct VolumeInfo {
     pub title: Option<String>,
@@ -20,6 +33,57 @@ pub struct VolumeInfo {
     pub url_vol: Option<String>,
     pub volume_no: u32,
     pub cover: Option<String>,
+    // 以下为卷详情页中的信息，解析目录时为空
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub last_update: Option<String>,
+    #[serde(default)]
+    pub word_count: Option<u64>,
+    #[serde(default)]
+    pub chapter_count: Option<u32>,
+}
+
+/// 搜索结果、排行榜等列表中的一本书
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct BookSummary {
+    pub id: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub cover: Option<String>,
+    pub tags: Vec<String>,
+    /// 连载中、已完结等
+    pub status: Option<String>,
+}
+
+/// 排行榜和分类列表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub enum BookListKind {
+    /// 最近更新
+    LastUpdate,
+    /// 新书
+    Newest,
+    /// 总点击
+    Popular,
+    /// 月点击
+    MonthVisit,
+    /// 收藏
+    Favorite,
+    /// 已完结
+    Finished,
+}
+
+/// 列表中的一页
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct BookList {
+    pub books: Vec<BookSummary>,
+    pub page: u32,
+    /// 无法解析分页时与`page`相同
+    pub total_pages: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,22 +107,131 @@ pub struct Volume {
     pub volume_no: u32,
     pub updated_at: String,
     pub path: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub last_update: Option<String>,
+    #[serde(default)]
+    pub word_count: Option<u64>,
+    #[serde(default)]
+    pub chapter_count: Option<u32>,
 }
 
-#[derive(Debug, Clone)]
-pub enum Content {
-    Tag(String),
+/// 段落中的行内内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Inline {
     Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    /// 注音，`base` 为正文，`annotation` 为注音
+    Ruby {
+        base: String,
+        annotation: String,
+    },
+    Link {
+        href: String,
+        children: Vec<Inline>,
+    },
+    LineBreak,
+    /// 译注等脚注，阅读器中以弹出的形式显示
+    Note(Vec<Inline>),
+}
+
+impl Inline {
+    /// 不含格式的文本，注音只保留正文，不包括脚注
+    pub fn text(&self) -> String {
+        match self {
+            Inline::Text(text) => text.clone(),
+            Inline::Bold(children) | Inline::Italic(children) | Inline::Link { children, .. } => {
+                inlines_text(children)
+            }
+            Inline::Ruby { base, .. } => base.clone(),
+            Inline::LineBreak | Inline::Note(_) => String::new(),
+        }
+    }
+
+    fn map_text(&mut self, f: &mut impl FnMut(&str) -> String) {
+        match self {
+            Inline::Text(text) => *text = f(text),
+            Inline::Bold(children)
+            | Inline::Italic(children)
+            | Inline::Link { children, .. }
+            | Inline::Note(children) => children.iter_mut().for_each(|inline| inline.map_text(f)),
+            Inline::Ruby { base, annotation } => {
+                *base = f(base);
+                *annotation = f(annotation);
+            }
+            Inline::LineBreak => (),
+        }
+    }
+}
+
+pub fn inlines_text(inlines: &[Inline]) -> String {
+    inlines.iter().map(Inline::text).collect()
+}
+
+/// 章节中的块级内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Content {
+    /// `<p>` 段落
+    Paragraph(Vec<Inline>),
+    /// `<p>` 以外的块级元素，不参与段落重排
+    Block(Vec<Inline>),
+    Heading {
+        level: u8,
+        children: Vec<Inline>,
+    },
+    /// 图片url
     Image(String),
+    Separator,
+    LineBreak,
 }
 
 impl Content {
+    pub fn paragraph(text: &str) -> Self {
+        Content::Paragraph(vec![Inline::Text(text.to_string())])
+    }
+
+    /// 不含格式的文本
+    pub fn text(&self) -> String {
+        match self {
+            Content::Paragraph(inlines)
+            | Content::Block(inlines)
+            | Content::Heading {
+                children: inlines, ..
+            } => inlines_text(inlines),
+            Content::Image(_) | Content::Separator | Content::LineBreak => String::new(),
+        }
+    }
+
     pub fn is_text(&self) -> bool {
-        matches!(self, Content::Text(_))
+        matches!(
+            self,
+            Content::Paragraph(_) | Content::Block(_) | Content::Heading { .. }
+        )
     }
 
+    /// 没有可见内容，只是空行或空段落
     pub fn is_empty(&self) -> bool {
-        matches!(self, Content::Text(text) if text.is_empty())
+        match self {
+            Content::LineBreak => true,
+            Content::Image(_) | Content::Separator => false,
+            _ => self.text().trim().is_empty(),
+        }
+    }
+
+    /// 修改所有文本，格式保持不变
+    pub fn map_text(&mut self, mut f: impl FnMut(&str) -> String) {
+        match self {
+            Content::Paragraph(inlines)
+            | Content::Block(inlines)
+            | Content::Heading {
+                children: inlines, ..
+            } => inlines
+                .iter_mut()
+                .for_each(|inline| inline.map_text(&mut f)),
+            Content::Image(_) | Content::Separator | Content::LineBreak => (),
+        }
     }
 }
 
@@ -124,7 +124,7 @@ impl ParagraphRestorer {
         }
 
         // 根据恢复的索引重新排列段落
-        let mut result = vec![Content::Text(String::new()); n];
+        let mut result = vec![Content::LineBreak; n];
         for (original_index, &correct_index) in indices.iter().enumerate() {
             result[correct_index] = paragraphs[original_index].clone();
         }
@@ -132,32 +132,9 @@ impl ParagraphRestorer {
         result
     }
 
+    /// 只有非空的 `<p>` 段落参与重排
     fn is_reorder_target(content: &Content) -> bool {
-        match content {
-            Content::Text(text) => !text.trim().is_empty(),
-            Content::Tag(tag) => {
-                let trimmed = tag.trim_start();
-                if !(trimmed.starts_with("<p") || trimmed.starts_with("<P")) {
-                    return false;
-                }
-
-                let mut in_tag = false;
-                for ch in tag.chars() {
-                    match ch {
-                        '<' => in_tag = true,
-                        '>' => in_tag = false,
-                        _ => {
-                            if !in_tag && !ch.is_whitespace() {
-                                return true;
-                            }
-                        }
-                    }
-                }
-
-                false
-            }
-            Content::Image(_) => false,
-        }
+        matches!(content, Content::Paragraph(_)) && !content.text().trim().is_empty()
     }
 
     pub fn restore_with_index(text: Vec<Content>, index_list: Vec<usize>) -> Vec<Content> {
@@ -197,7 +174,7 @@ mod tests {
 
         let mut text = vec![];
         let mut img_list = vec![];
-        parse::parse_novel_text(&html, &mut text, &mut img_list, &config.base_url);
+        parse::parse_novel_text(&html, &mut text, &mut img_list, client.profile());
 
         let restorer = ParagraphRestorer::new(2);
         // 恢复正确顺序
@@ -238,34 +215,9 @@ mod tests {
             .iter()
             .filter(|content| ParagraphRestorer::is_reorder_target(content))
             .take(expected.len())
-            .map(extract_plain_text)
+            .map(|content| content.text().trim().to_string())
             .collect();
 
         assert_eq!(actual, expected, "段落恢复顺序不符合预期");
     }
-
-    fn extract_plain_text(content: &Content) -> String {
-        match content {
-            Content::Text(text) => text.trim().to_string(),
-            Content::Tag(tag) => {
-                let mut in_tag = false;
-                let mut result = String::new();
-
-                for ch in tag.chars() {
-                    match ch {
-                        '<' => in_tag = true,
-                        '>' => in_tag = false,
-                        _ => {
-                            if !in_tag {
-                                result.push(ch);
-                            }
-                        }
-                    }
-                }
-
-                result.trim().to_string()
-            }
-            Content::Image(_) => String::new(),
-        }
-    }
 }
@@ -1,65 +1,75 @@
-use std::collections::HashMap;
+use std::{collections::HashSet, sync::LazyLock};
 
-use scraper::{Html, Selector};
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
 
 use crate::{
-    model::{BookInfo, Content, VolumeInfo},
-    utils,
+    model::{BookInfo, BookList, BookSummary, Content, Inline, VolumeInfo},
+    secret::decode_text,
+    site_profile::{Markers, SiteProfile, selector},
 };
 
-pub fn parse_metadata(html: &str) -> BookInfo {
+static DATE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d{4}-\d{1,2}-\d{1,2}").unwrap());
+static PAGE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)(?:_0)?\.html").unwrap());
+static CHAPTER_COUNT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)\s*章").unwrap());
+static WORD_COUNT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([\d.]+)\s*([万萬])?字").unwrap());
+
+pub fn parse_metadata(html: &str, profile: &SiteProfile) -> BookInfo {
     let document = Html::parse_document(html);
-    let h1_selector = Selector::parse("h1").unwrap();
-    let span_selector = Selector::parse("span").unwrap();
-    let content_selector = Selector::parse("content").unwrap();
-    let em_selector = Selector::parse("em").unwrap();
-    let img_selector = Selector::parse("img").unwrap();
+    let selectors = &profile.selectors;
 
-    let mut title: Option<String> = None;
-    let mut author: Option<String> = None;
-    let mut publisher: Option<String> = None;
-    let mut tags: Vec<String> = Vec::new();
-    let mut description: Option<String> = None;
-    let mut cover: Option<String> = None;
+    let first_text = |s: &str| {
+        document
+            .select(&selector(s))
+            .next()
+            .map(|element| element.text().collect::<String>())
+    };
+    let title = first_text(&selectors.book_title);
+    let author = first_text(&selectors.author);
+    let description = first_text(&selectors.description);
+    let cover = document
+        .select(&selector(&selectors.cover))
+        .find_map(|element| element.value().attr("src"))
+        .map(|src| src.to_string());
+    let publisher = document
+        .select(&selector(&selectors.publisher))
+        .next_back()
+        .map(|element| element.text().collect::<String>());
+    let tags = document
+        .select(&selector(&selectors.tag))
+        .map(|element| element.text().collect::<String>())
+        .collect();
+
+    let status = document
+        .select(&selector(&selectors.status))
+        .map(|element| element.text().collect::<String>().trim().to_string())
+        .find(|text| Markers::matches(&profile.markers.status, text));
+    let illustrator = document
+        .select(&selector(&selectors.illustrator))
+        .next()
+        .map(|element| element.text().collect::<String>().trim().to_string())
+        .filter(|text| !text.is_empty());
+    let aliases = document
+        .select(&selector(&selectors.alias))
+        .flat_map(|element| {
+            let text = element.text().collect::<String>();
+            let text = text.trim();
+            let text = text
+                .strip_prefix("别名：")
+                .or(text.strip_prefix("又名："))
+                .unwrap_or(text)
+                .to_string();
+            text.split(['/', '、', '|'])
+                .map(|alias| alias.trim().to_string())
+                .filter(|alias| !alias.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let meta = book_meta_text(&document, profile);
+    let last_update = parse_last_update(html, profile)
+        .or_else(|| DATE_RE.find(&meta).map(|date| date.as_str().to_string()));
 
-    for element in document.select(&h1_selector) {
-        if let Some(property) = element.value().attr("class")
-            && property == "book-title"
-        {
-            title = Some(element.text().collect::<String>());
-            break;
-        }
-    }
-    for element in document.select(&span_selector) {
-        if let Some(property) = element.value().attr("class")
-            && property == "authorname"
-        {
-            author = Some(element.text().collect::<String>());
-            break;
-        }
-    }
-    for element in document.select(&img_selector) {
-        if let Some(property) = element.value().attr("class")
-            && property == "book-cover"
-            && let Some(property) = element.value().attr("src")
-        {
-            cover = Some(property.to_string());
-            break;
-        }
-    }
-    for element in document.select(&em_selector) {
-        if let Some(property) = element.value().attr("class") {
-            if property == "tag-small orange" {
-                publisher = Some(element.text().collect::<String>());
-            }
-            if property == "tag-small red" {
-                tags.push(element.text().collect::<String>());
-            }
-        }
-    }
-    if let Some(element) = document.select(&content_selector).next() {
-        description = Some(element.text().collect::<String>());
-    }
     BookInfo {
         title,
         author,
@@ -67,61 +77,63 @@ pub fn parse_metadata(html: &str) -> BookInfo {
         tags,
         description,
         cover,
+        status,
+        word_count: parse_word_count(&meta),
+        last_update,
+        illustrator,
+        aliases,
     }
 }
 
-pub fn parse_volume_list(html: &str) -> Vec<VolumeInfo> {
+pub fn parse_volume_list(html: &str, profile: &SiteProfile) -> Vec<VolumeInfo> {
     let document = Html::parse_document(html);
-    let ul_selector = Selector::parse("ul").unwrap();
-    let li_selector = Selector::parse("li").unwrap();
+    let selectors = &profile.selectors;
+    let title_selector = selector(&selectors.volume_title);
+    let cover_selector = selector(&selectors.volume_cover);
+    let chapter_selector = selector(&selectors.chapter);
     let a_selector = Selector::parse("a").unwrap();
     let img_selector = Selector::parse("img").unwrap();
     let mut volume_list: Vec<VolumeInfo> = Vec::new();
 
-    for element in document.select(&ul_selector) {
-        if let Some(property) = element.value().attr("class")
-            && property == "volume-chapters"
-        {
-            let mut title = None;
-            let mut url_vol = None;
-            let mut chapter_list: Vec<String> = Vec::new();
-            let mut chapter_path_list = Vec::new();
-            let mut cover = None;
-            for element in element.select(&li_selector) {
-                if let Some(property) = element.value().attr("class") {
-                    if property == "chapter-bar chapter-li" {
-                        title = Some(element.text().collect::<String>());
-                    }
-                    if property == "volume-cover chapter-li" {
-                        if let Some(element) = element.select(&a_selector).next() {
-                            url_vol = Some(element.value().attr("href").unwrap().to_string());
-                        }
-                        for element in element.select(&img_selector) {
-                            if let Some(src) = element.value().attr("data-src") {
-                                cover = Some(src.to_string());
-                            } else if let Some(src) = element.value().attr("src") {
-                                cover = Some(src.to_string());
-                            }
-                        }
-                    }
-                    if property == "chapter-li jsChapter" {
-                        chapter_list.push(element.text().collect::<String>());
-                        if let Some(element) = element.select(&a_selector).next() {
-                            chapter_path_list
-                                .push(element.value().attr("href").unwrap().to_string());
-                        }
-                    }
+    for element in document.select(&selector(&selectors.volume)) {
+        let title = element
+            .select(&title_selector)
+            .last()
+            .map(|element| element.text().collect::<String>());
+        let mut url_vol = None;
+        let mut chapter_list: Vec<String> = Vec::new();
+        let mut chapter_path_list = Vec::new();
+        let mut cover = None;
+        for element in element.select(&cover_selector) {
+            if let Some(element) = element.select(&a_selector).next() {
+                url_vol = Some(element.value().attr("href").unwrap().to_string());
+            }
+            for element in element.select(&img_selector) {
+                if let Some(src) = element.value().attr("data-src") {
+                    cover = Some(src.to_string());
+                } else if let Some(src) = element.value().attr("src") {
+                    cover = Some(src.to_string());
                 }
             }
-            volume_list.push(VolumeInfo {
-                title,
-                chapter_list,
-                chapter_path_list,
-                url_vol,
-                volume_no: (volume_list.len() + 1).try_into().unwrap(),
-                cover,
-            });
         }
+        for element in element.select(&chapter_selector) {
+            chapter_list.push(element.text().collect::<String>());
+            if let Some(element) = element.select(&a_selector).next() {
+                chapter_path_list.push(element.value().attr("href").unwrap().to_string());
+            }
+        }
+        volume_list.push(VolumeInfo {
+            title,
+            chapter_list,
+            chapter_path_list,
+            url_vol,
+            volume_no: (volume_list.len() + 1).try_into().unwrap(),
+            cover,
+            description: None,
+            last_update: None,
+            word_count: None,
+            chapter_count: None,
+        });
     }
     volume_list
 }
@@ -130,121 +142,888 @@ pub fn parse_novel_text(
     html: &str,
     text: &mut Vec<Content>,
     img_list: &mut Vec<String>,
-    _url_base: &str,
+    profile: &SiteProfile,
 ) {
     let document = Html::parse_document(html);
-    let div_selector = Selector::parse("div").unwrap();
+    let rules = NoteRules {
+        selector: selector(&profile.selectors.note),
+        pattern: &profile.regex().inline_note,
+    };
 
-    for element in document.select(&div_selector) {
-        if let Some(property) = element.value().attr("id")
-            && property == "acontent"
-        {
-            for child in element.child_elements() {
-                if child.value().name() == "img" {
-                    let mut img = None;
-                    if let Some(data_src) = child.value().attr("data-src") {
-                        img = Some(data_src.to_string());
-                    } else if let Some(src) = child.value().attr("src") {
-                        img = Some(src.to_string());
-                    }
-                    if let Some(img) = img {
-                        // if error_img.contains(&img) {
-                        //     continue;
-                        // }
-                        text.push(Content::Image(img.clone()));
-                        img_list.push(img);
-                    }
-                } else if (child.value().name().len() > 1 && child.value().name().contains("p"))
-                    || (child.value().name() == "div" && child.value().attr("class").is_some())
-                {
+    for element in document.select(&selector(&profile.selectors.content)) {
+        for child in element.child_elements() {
+            let name = child.value().name();
+            if name == "img" {
+                let mut img = None;
+                if let Some(data_src) = child.value().attr("data-src") {
+                    img = Some(data_src.to_string());
+                } else if let Some(src) = child.value().attr("src") {
+                    img = Some(src.to_string());
+                }
+                if let Some(img) = img {
+                    // if error_img.contains(&img) {
+                    //     continue;
+                    // }
+                    text.push(Content::Image(img.clone()));
+                    img_list.push(img);
+                }
+            } else if (name.len() > 1 && name.contains("p"))
+                || (name == "div" && child.value().attr("class").is_some())
+            {
+                continue;
+            } else {
+                let t = child.text().collect::<String>();
+                if Markers::matches(&profile.markers.skip_text, &t) {
+                    continue;
+                }
+                text.push(parse_block(child, t.is_empty(), &rules));
+            }
+        }
+    }
+}
+
+/// 识别正文中注释的规则
+struct NoteRules<'a> {
+    selector: Selector,
+    pattern: &'a Regex,
+}
+
+/// 把正文中的一个块级元素转换为 [`Content`]
+fn parse_block(element: ElementRef, is_empty: bool, rules: &NoteRules) -> Content {
+    let name = element.value().name();
+    match name {
+        "br" => Content::LineBreak,
+        "hr" => Content::Separator,
+        _ if is_empty => Content::LineBreak,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Content::Heading {
+            level: name[1..].parse().unwrap_or(1),
+            children: parse_paragraph(element, rules),
+        },
+        "p" => Content::Paragraph(parse_paragraph(element, rules)),
+        _ => Content::Block(parse_paragraph(element, rules)),
+    }
+}
+
+/// 解析段落的内容，只去掉段落和换行前后的空白
+fn parse_paragraph(element: ElementRef, rules: &NoteRules) -> Vec<Inline> {
+    let mut result = Vec::new();
+    let mut line = Vec::new();
+    for inline in parse_inlines(element, rules) {
+        if inline == Inline::LineBreak {
+            trim_line(&mut line);
+            result.append(&mut line);
+            result.push(inline);
+        } else {
+            line.push(inline);
+        }
+    }
+    trim_line(&mut line);
+    result.append(&mut line);
+    result
+}
+
+fn trim_line(line: &mut Vec<Inline>) {
+    trim_start(line);
+    trim_end(line);
+}
+
+fn trim_start(line: &mut Vec<Inline>) {
+    while let Some(first) = line.first_mut() {
+        match first {
+            Inline::Text(text) => {
+                if text.trim_start().is_empty() {
+                    line.remove(0);
                     continue;
-                } else {
-                    let t = child.text().collect::<String>();
-                    if t.contains("function")
-                        || t.contains("Note: 请不要")
-                        || t.contains("= window.")
-                    {
-                        continue;
+                }
+                *text = text.trim_start().to_string();
+            }
+            Inline::Bold(children) | Inline::Italic(children) | Inline::Link { children, .. } => {
+                trim_start(children)
+            }
+            _ => (),
+        }
+        break;
+    }
+}
+
+fn trim_end(line: &mut Vec<Inline>) {
+    while let Some(last) = line.last_mut() {
+        match last {
+            Inline::Text(text) => {
+                if text.trim_end().is_empty() {
+                    line.pop();
+                    continue;
+                }
+                text.truncate(text.trim_end().len());
+            }
+            Inline::Bold(children) | Inline::Italic(children) | Inline::Link { children, .. } => {
+                trim_end(children)
+            }
+            _ => (),
+        }
+        break;
+    }
+}
+
+/// 把连续的空白（包括换行）合并为一个空格，和浏览器的显示一致
+///
+/// 只处理 HTML 中的空白字符，全角空格等保留原样。
+fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0c') {
+            space = true;
+            continue;
+        }
+        if space {
+            result.push(' ');
+            space = false;
+        }
+        result.push(c);
+    }
+    if space {
+        result.push(' ');
+    }
+    result
+}
+
+fn parse_inlines(element: ElementRef, rules: &NoteRules) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    for node in element.children() {
+        if let Some(text) = node.value().as_text() {
+            let text = collapse_whitespace(text);
+            if !text.is_empty() {
+                push_text(&mut inlines, &text, rules);
+            }
+            continue;
+        }
+        let Some(child) = ElementRef::wrap(node) else {
+            continue;
+        };
+        if rules.selector.matches(&child) {
+            match child.value().attr("title") {
+                // 被注释的文字保留在正文中，`sup` 一般只是注释的编号
+                Some(title) => {
+                    if child.value().name() != "sup" {
+                        inlines.extend(parse_inlines(child, rules));
                     }
-                    let raw_text = child.text();
-                    let mut texts = HashMap::new();
-                    for text in raw_text {
-                        texts.insert(text, utils::escape_epub_text(text.trim()));
+                    inlines.push(Inline::Note(vec![Inline::Text(title.trim().to_string())]));
+                }
+                None => inlines.push(Inline::Note(parse_paragraph(child, rules))),
+            }
+            continue;
+        }
+        match child.value().name() {
+            "b" | "strong" => inlines.push(Inline::Bold(parse_inlines(child, rules))),
+            "i" | "em" => inlines.push(Inline::Italic(parse_inlines(child, rules))),
+            "br" => inlines.push(Inline::LineBreak),
+            "a" => inlines.push(Inline::Link {
+                href: child.value().attr("href").unwrap_or_default().to_string(),
+                children: parse_inlines(child, rules),
+            }),
+            "ruby" => {
+                let mut base = String::new();
+                let mut annotation = String::new();
+                for node in child.children() {
+                    if let Some(text) = node.value().as_text() {
+                        base.push_str(text.trim());
+                    } else if let Some(element) = ElementRef::wrap(node) {
+                        match element.value().name() {
+                            "rt" => annotation.push_str(element.text().collect::<String>().trim()),
+                            "rp" => (),
+                            _ => base.push_str(element.text().collect::<String>().trim()),
+                        }
                     }
+                }
+                inlines.push(Inline::Ruby { base, annotation });
+            }
+            // span、font 等只保留内容
+            _ => inlines.extend(parse_inlines(child, rules)),
+        }
+    }
+    inlines
+}
 
-                    let mut html = child.html();
-                    if let Some(class) = child.value().attr("class") {
-                        html = html.replace(format!(" class=\"{}\"", class).as_str(), "");
-                    }
+/// 添加文本，括号中的译注转换为脚注
+fn push_text(inlines: &mut Vec<Inline>, text: &str, rules: &NoteRules) {
+    let mut last = 0;
+    for captures in rules.pattern.captures_iter(text) {
+        let matched = captures.get(0).unwrap();
+        let before = text[last..matched.start()].trim_end();
+        if !before.is_empty() {
+            inlines.push(Inline::Text(before.to_string()));
+        }
+        inlines.push(Inline::Note(vec![Inline::Text(
+            captures[1].trim().to_string(),
+        )]));
+        last = matched.end();
+    }
+    let rest = &text[last..];
+    if !rest.is_empty() {
+        inlines.push(Inline::Text(rest.to_string()));
+    }
+}
 
-                    for (raw_t, t) in texts {
-                        html = html.replace(raw_t, &t);
-                    }
+/// 解析搜索结果页
+///
+/// 只有一个结果时站点会直接跳转到书籍页面，此时从书籍页面中解析。
+pub fn parse_search_result(html: &str, profile: &SiteProfile) -> Vec<BookSummary> {
+    let document = Html::parse_document(html);
+    let result = parse_book_items(&document, profile);
+    if !result.is_empty() {
+        return result;
+    }
 
-                    if t.is_empty() {
-                        html = String::from("<br/>");
-                    }
+    let id = document
+        .select(&selector(&profile.selectors.canonical))
+        .filter_map(|e| e.value().attr("href").or(e.value().attr("content")))
+        .find_map(|url| book_id_from_url(url, profile));
+    let info = parse_metadata(html, profile);
+    match (id, info.title) {
+        (Some(id), Some(title)) => vec![BookSummary {
+            id,
+            title,
+            author: info.author,
+            cover: info.cover,
+            tags: info.tags,
+            status: info.status,
+        }],
+        _ => Vec::new(),
+    }
+}
 
-                    text.push(Content::Tag(html));
-                }
+/// 解析排行榜和分类列表页
+pub fn parse_book_list(html: &str, page: u32, profile: &SiteProfile) -> BookList {
+    let document = Html::parse_document(html);
+    let books = parse_book_items(&document, profile);
+
+    // 分页链接中的最大页码即为总页数
+    let total_pages = document
+        .select(&selector(&profile.selectors.page_link))
+        .filter_map(|a| {
+            let text = a.text().collect::<String>();
+            text.trim().parse::<u32>().ok().or_else(|| {
+                let href = a.value().attr("href")?;
+                PAGE_RE.captures(href)?[1].parse().ok()
+            })
+        })
+        .max()
+        .unwrap_or(page)
+        .max(page);
+    BookList {
+        books,
+        page,
+        total_pages,
+    }
+}
+
+/// 解析作者页中的作品列表，同一本书只保留一次
+pub fn parse_author_books(html: &str, profile: &SiteProfile) -> Vec<BookSummary> {
+    let document = Html::parse_document(html);
+    let mut ids = HashSet::new();
+    parse_book_items(&document, profile)
+        .into_iter()
+        .filter(|book| ids.insert(book.id.clone()))
+        .collect()
+}
+
+fn book_id_from_url(url: &str, profile: &SiteProfile) -> Option<String> {
+    profile
+        .regex()
+        .book_id
+        .captures(url)
+        .map(|captures| captures[1].to_string())
+}
+
+/// 解析搜索结果和排行榜中的书籍列表
+fn parse_book_items(document: &Html, profile: &SiteProfile) -> Vec<BookSummary> {
+    let selectors = &profile.selectors;
+    let a_selector = Selector::parse("a").unwrap();
+    let title_selector = selector(&selectors.book_item_title);
+    let author_selector = selector(&selectors.book_item_author);
+    let img_selector = Selector::parse("img").unwrap();
+    let tag_selector = selector(&selectors.book_item_tag);
+
+    let mut result = Vec::new();
+    for element in document.select(&selector(&selectors.book_item)) {
+        let Some(id) = element
+            .select(&a_selector)
+            .filter_map(|a| a.value().attr("href"))
+            .find_map(|href| book_id_from_url(href, profile))
+        else {
+            continue;
+        };
+        let Some(title) = element
+            .select(&title_selector)
+            .next()
+            .map(|e| e.text().collect::<String>().trim().to_string())
+        else {
+            continue;
+        };
+        let author = element
+            .select(&author_selector)
+            .next()
+            .map(|e| e.text().collect::<String>().trim().to_string());
+        let cover = element.select(&img_selector).next().and_then(|img| {
+            img.value()
+                .attr("data-src")
+                .or(img.value().attr("src"))
+                .map(|s| s.to_string())
+        });
+        let mut tags = Vec::new();
+        let mut status = None;
+        for tag in element.select(&tag_selector) {
+            let tag = tag.text().collect::<String>().trim().to_string();
+            if Markers::matches(&profile.markers.status, &tag) {
+                status = Some(tag);
+            } else if !tag.is_empty() {
+                tags.push(tag);
             }
         }
+        result.push(BookSummary {
+            id,
+            title,
+            author,
+            cover,
+            tags,
+            status,
+        });
+    }
+    result
+}
+
+/// 解析卷详情页，写入简介、最后更新日期、字数、章节数和高清封面
+pub fn parse_volume_page(html: &str, volume: &mut VolumeInfo, profile: &SiteProfile) {
+    volume.description = parse_vol_desc(html, profile);
+    volume.last_update = parse_last_update(html, profile);
+
+    let document = Html::parse_document(html);
+    if let Some(element) = document.select(&selector(&profile.selectors.cover)).next() {
+        let element = element.value();
+        // 懒加载时 src 为缩略图或占位图
+        if let Some(cover) = element
+            .attr("data-original")
+            .or(element.attr("data-src"))
+            .or(element.attr("src"))
+        {
+            volume.cover = Some(cover.to_string());
+        }
+    }
+
+    let meta = book_meta_text(&document, profile);
+    volume.word_count = parse_word_count(&meta);
+    if let Some(captures) = CHAPTER_COUNT_RE.captures(&meta) {
+        volume.chapter_count = captures[1].parse().ok();
     }
 }
 
-pub fn parse_vol_desc(html: &str) -> Option<String> {
+/// 所有 `.book-meta` 中的文字
+fn book_meta_text(document: &Html, profile: &SiteProfile) -> String {
+    document
+        .select(&selector(&profile.selectors.book_meta))
+        .map(|element| element.text().collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 解析 `12.5万字`、`3000字` 形式的字数
+fn parse_word_count(text: &str) -> Option<u64> {
+    let captures = WORD_COUNT_RE.captures(text)?;
+    let count = captures[1].parse::<f64>().ok()?;
+    let unit = if captures.get(2).is_some() {
+        10000.0
+    } else {
+        1.0
+    };
+    Some((count * unit).round() as u64)
+}
+
+pub fn parse_vol_desc(html: &str, profile: &SiteProfile) -> Option<String> {
     let document = Html::parse_document(html);
-    let content_selector = Selector::parse("content").unwrap();
-    if let Some(element) = document.select(&content_selector).next() {
+    if let Some(element) = document
+        .select(&selector(&profile.selectors.description))
+        .next()
+    {
         let description = Some(element.text().collect::<String>());
         return description;
     }
     None
 }
 
-pub fn parse_last_update(novel_html: &str) -> Option<String> {
+pub fn parse_last_update(novel_html: &str, profile: &SiteProfile) -> Option<String> {
     let document = Html::parse_document(novel_html);
-    let a_selector = Selector::parse("a.book-meta.book-status").unwrap();
-    let div_selector = Selector::parse("div.book-meta-l").unwrap();
-    for element in document.select(&a_selector) {
-        for element in element.select(&div_selector) {
-            let mut direct_text = String::new();
-            for child in element.children() {
-                println!("child: {:?}", child.value());
-                if child.value().is_text() {
-                    direct_text.push_str(child.value().as_text().unwrap());
-                }
-            }
-            let cleaned_text = direct_text.trim();
-            if !cleaned_text.is_empty() {
-                return Some(cleaned_text.to_string());
+    for element in document.select(&selector(&profile.selectors.last_update)) {
+        let mut direct_text = String::new();
+        for child in element.children() {
+            if child.value().is_text() {
+                direct_text.push_str(child.value().as_text().unwrap());
             }
         }
+        let cleaned_text = direct_text.trim();
+        if !cleaned_text.is_empty() {
+            return Some(cleaned_text.to_string());
+        }
     }
     None
 }
 
+/// 解密使用了混淆字体的正文
+///
+/// 页面样式中使用了加密字体时，只有最后一个有内容的段落是加密的。
+pub fn decode_obfuscated_text(html: &str, contents: &mut [Content], profile: &SiteProfile) {
+    if !Markers::matches(&profile.markers.font_obfuscation, html) {
+        return;
+    }
+    if let Some(content) = contents
+        .iter_mut()
+        .rev()
+        .find(|content| content.is_text() && !content.is_empty())
+    {
+        content.map_text(decode_text);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::client;
-
-    #[ignore]
-    #[tokio::test]
-    async fn test_parse_last_update() {
-        let html = client::BiliClient::new(
-            "https://www.bilinovel.com",
-            "",
-            "",
-            &HashMap::new(),
-            false,
-            false,
-        )
-        .unwrap()
-        .get_novel("1", None)
-        .await
-        .unwrap();
-        let last_update = parse_last_update(&html);
-        println!("last_update: {:?}", last_update);
+
+    fn chapter(html: &str) -> (Vec<Content>, Vec<String>) {
+        let mut text = Vec::new();
+        let mut img_list = Vec::new();
+        parse_novel_text(html, &mut text, &mut img_list, &SiteProfile::default());
+        (text, img_list)
+    }
+
+    fn plain(s: &str) -> Inline {
+        Inline::Text(String::from(s))
+    }
+
+    #[test]
+    fn test_parse_metadata_fixture() {
+        let info = parse_metadata(
+            include_str!("../tests/fixtures/parse/book.html"),
+            &SiteProfile::default(),
+        );
+        assert_eq!(
+            info,
+            BookInfo {
+                title: Some(String::from("关于我转生变成史莱姆这档事")),
+                author: Some(String::from("伏瀬")),
+                publisher: Some(String::from("GC NOVELS")),
+                tags: vec![
+                    String::from("奇幻"),
+                    String::from("转生"),
+                    String::from("冒险")
+                ],
+                description: Some(String::from(
+                    "上班族三上悟被随机杀人魔刺死，转生到异世界成了一只史莱姆。"
+                )),
+                cover: Some(String::from(
+                    "https://www.bilinovel.com/files/article/image/1/1s.jpg"
+                )),
+                status: Some(String::from("连载中")),
+                word_count: Some(1234000),
+                last_update: Some(String::from("2024-05-01")),
+                illustrator: Some(String::from("みっつばー")),
+                aliases: vec![
+                    String::from("転生したらスライムだった件"),
+                    String::from("转生史莱姆")
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_volume_list_fixture() {
+        let volume_list = parse_volume_list(
+            include_str!("../tests/fixtures/parse/catalog.html"),
+            &SiteProfile::default(),
+        );
+        assert_eq!(
+            volume_list,
+            vec![
+                VolumeInfo {
+                    title: Some(String::from("第一卷")),
+                    chapter_list: vec![
+                        String::from("插图"),
+                        String::from("序章 死亡与转生"),
+                        String::from("第一章 我的名字是利姆露"),
+                    ],
+                    chapter_path_list: vec![
+                        String::from("/novel/1/100.html"),
+                        String::from("/novel/1/101.html"),
+                        String::from("/novel/1/102.html"),
+                    ],
+                    url_vol: Some(String::from("/novel/1/vol_10.html")),
+                    volume_no: 1,
+                    // 懒加载的封面取 data-src
+                    cover: Some(String::from("https://img.example.com/1/vol_10.jpg")),
+                    description: None,
+                    last_update: None,
+                    word_count: None,
+                    chapter_count: None,
+                },
+                VolumeInfo {
+                    title: Some(String::from("第二卷")),
+                    chapter_list: vec![String::from("插图"), String::from("第一章 哥布林村")],
+                    // 未公开链接的章节需要从上一章的下一页链接获取
+                    chapter_path_list: vec![
+                        String::from("javascript:cid(0)"),
+                        String::from("/novel/1/201.html"),
+                    ],
+                    url_vol: Some(String::from("/novel/1/vol_20.html")),
+                    volume_no: 2,
+                    cover: Some(String::from("https://img.example.com/1/vol_20.jpg")),
+                    description: None,
+                    last_update: None,
+                    word_count: None,
+                    chapter_count: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_search_result_fixture() {
+        let result = parse_search_result(
+            include_str!("../tests/fixtures/parse/search.html"),
+            &SiteProfile::default(),
+        );
+        assert_eq!(
+            result,
+            vec![
+                BookSummary {
+                    id: String::from("1"),
+                    title: String::from("关于我转生变成史莱姆这档事"),
+                    author: Some(String::from("伏瀬")),
+                    cover: Some(String::from("https://img.example.com/1/1s.jpg")),
+                    tags: vec![String::from("奇幻"), String::from("转生")],
+                    status: Some(String::from("连载中")),
+                },
+                BookSummary {
+                    id: String::from("2"),
+                    title: String::from("转生史莱姆日记"),
+                    author: Some(String::from("柴")),
+                    cover: None,
+                    tags: vec![],
+                    status: Some(String::from("已完结")),
+                },
+            ]
+        );
+
+        // 只有一个结果时跳转到书籍页面
+        let html = include_str!("../tests/fixtures/parse/book.html");
+        let result = parse_search_result(html, &SiteProfile::default());
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].id, "1");
+        assert_eq!(result[0].title, "关于我转生变成史莱姆这档事");
+        assert_eq!(result[0].author, Some(String::from("伏瀬")));
+
+        assert!(
+            parse_search_result(
+                "<html><body>没有找到</body></html>",
+                &SiteProfile::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_author_books_fixture() {
+        let books = parse_author_books(
+            include_str!("../tests/fixtures/parse/author.html"),
+            &SiteProfile::default(),
+        );
+        assert_eq!(
+            books
+                .iter()
+                .map(|book| (book.id.as_str(), book.status.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![("1", Some("连载中")), ("5", Some("已完结"))]
+        );
+        assert!(
+            books
+                .iter()
+                .all(|book| book.author.as_deref() == Some("伏瀬"))
+        );
+    }
+
+    #[test]
+    fn test_parse_book_list_fixture() {
+        let list = parse_book_list(
+            include_str!("../tests/fixtures/parse/top.html"),
+            2,
+            &SiteProfile::default(),
+        );
+        assert_eq!(list.page, 2);
+        assert_eq!(list.total_pages, 50);
+        assert_eq!(
+            list.books
+                .iter()
+                .map(|book| (book.id.as_str(), book.title.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("3", "魔女之旅"), ("4", "弱角友崎同学")]
+        );
+        assert_eq!(list.books[0].status, Some(String::from("已完结")));
+        assert_eq!(list.books[1].tags, vec![String::from("校园")]);
+
+        // 没有分页时只有当前页
+        let list = parse_book_list(
+            include_str!("../tests/fixtures/parse/search.html"),
+            1,
+            &SiteProfile::default(),
+        );
+        assert_eq!(list.total_pages, 1);
+        assert_eq!(list.books.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_volume_page_fixture() {
+        let html = include_str!("../tests/fixtures/parse/volume.html");
+        assert_eq!(
+            parse_vol_desc(html, &SiteProfile::default()),
+            Some(String::from("第一卷 简介：被刺死的三上悟转生为史莱姆。"))
+        );
+        assert_eq!(
+            parse_last_update(html, &SiteProfile::default()),
+            Some(String::from("2024-05-01"))
+        );
+        assert_eq!(
+            parse_last_update("<html></html>", &SiteProfile::default()),
+            None
+        );
+
+        let catalog = include_str!("../tests/fixtures/parse/catalog.html");
+        let mut volume = parse_volume_list(catalog, &SiteProfile::default()).remove(0);
+        parse_volume_page(html, &mut volume, &SiteProfile::default());
+        assert_eq!(
+            volume.description,
+            Some(String::from("第一卷 简介：被刺死的三上悟转生为史莱姆。"))
+        );
+        assert_eq!(volume.last_update, Some(String::from("2024-05-01")));
+        assert_eq!(volume.word_count, Some(125000));
+        assert_eq!(volume.chapter_count, Some(6));
+        assert_eq!(
+            volume.cover,
+            Some(String::from("https://img.example.com/1/vol_10_big.jpg"))
+        );
+    }
+
+    #[test]
+    fn test_parse_chapter_fixture() {
+        let (text, img_list) = chapter(include_str!("../tests/fixtures/parse/chapter.html"));
+        // 广告、脚本和提示文字会被跳过
+        assert_eq!(
+            text,
+            vec![
+                Content::Paragraph(vec![
+                    plain("我叫三上悟，"),
+                    Inline::Bold(vec![plain("三十七岁")]),
+                    plain("。"),
+                ]),
+                Content::Paragraph(vec![
+                    plain("这天我被"),
+                    Inline::Ruby {
+                        base: String::from("随机杀人魔"),
+                        annotation: String::from("通り魔"),
+                    },
+                    plain("刺中了。"),
+                ]),
+                Content::LineBreak,
+                Content::Paragraph(vec![]),
+                Content::Paragraph(vec![
+                    plain("「"),
+                    Inline::Italic(vec![plain("好痛")]),
+                    plain("……」"),
+                ]),
+                Content::paragraph("《待续》"),
+            ]
+        );
+        assert!(img_list.is_empty());
+
+        let (text, img_list) = chapter(include_str!("../tests/fixtures/parse/chapter_2.html"));
+        assert_eq!(
+            text,
+            vec![
+                Content::paragraph("分页的第一段。"),
+                Content::Image(String::from("//img.example.com/1/101_2.jpg")),
+                Content::Separator,
+                Content::paragraph("分页的最后一段。"),
+            ]
+        );
+        assert_eq!(
+            img_list,
+            vec![String::from("//img.example.com/1/101_2.jpg")]
+        );
+    }
+
+    #[test]
+    fn test_parse_notes_fixture() {
+        let (text, _) = chapter(include_str!("../tests/fixtures/parse/notes.html"));
+        let note = |s: &str| Inline::Note(vec![plain(s)]);
+        assert_eq!(
+            text,
+            vec![
+                Content::Paragraph(vec![
+                    plain("她叫我「前辈」"),
+                    note("原文为「先輩」，指同社团的学长"),
+                    plain("。"),
+                ]),
+                Content::Paragraph(vec![
+                    plain("这是"),
+                    plain("御宅族"),
+                    note("otaku"),
+                    plain("的常识"),
+                    note("1"),
+                    plain("。"),
+                ]),
+                Content::Paragraph(vec![
+                    plain("魔法"),
+                    note("出自第一卷"),
+                    plain("发动了"),
+                    Inline::Note(vec![plain("参见"), Inline::Italic(vec![plain("设定集")]),]),
+                    plain("。"),
+                ]),
+                Content::paragraph("（这是普通的括号）"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_illustration_fixture() {
+        let (text, img_list) = chapter(include_str!("../tests/fixtures/parse/illustration.html"));
+        let images = vec![
+            String::from("https://img.example.com/1/100_1.jpg"),
+            String::from("https://img.example.com/1/100_2.jpg"),
+            String::from("https://img.example.com/1/100_3.png"),
+        ];
+        assert_eq!(
+            text,
+            vec![
+                Content::Image(images[0].clone()),
+                Content::Image(images[1].clone()),
+                Content::LineBreak,
+                Content::paragraph("插画：みっつばー"),
+                Content::Image(images[2].clone()),
+            ]
+        );
+        assert_eq!(img_list, images);
+    }
+
+    #[test]
+    fn test_decode_obfuscated_fixture() {
+        let html = include_str!("../tests/fixtures/parse/obfuscated.html");
+        let (mut text, _) = chapter(html);
+        // 加密段落中混入的普通汉字会在解密时删除
+        assert_eq!(
+            text[1],
+            Content::paragraph("\u{e005}\u{f560}\u{e874}的\u{f065}\u{e000}。")
+        );
+
+        decode_obfuscated_text(html, &mut text, &SiteProfile::default());
+        assert_eq!(
+            text,
+            vec![
+                Content::paragraph("第一段没有加密。"),
+                Content::paragraph("阳光下的狼。"),
+                Content::LineBreak,
+            ]
+        );
+
+        // 没有加密字体的页面不做处理
+        let html = include_str!("../tests/fixtures/parse/chapter_2.html");
+        let (mut text, _) = chapter(html);
+        let expected = text.clone();
+        decode_obfuscated_text(html, &mut text, &SiteProfile::default());
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_parse_novel_text() {
+        let html = r#"<div id="acontent">
+            <p class="x">第一段 &amp; <b>粗体</b><i>斜体</i></p>
+            <p><ruby>漢字<rp>(</rp><rt>かんじ</rt><rp>)</rp></ruby><a href="/novel/1.html">链接</a></p>
+            <br>
+            <p></p>
+            <h3>小标题</h3>
+            <hr>
+            <img data-src="https://img.example.com/1.jpg">
+            <div class="ad">广告</div>
+            <div>没有class的div</div>
+        </div>"#;
+        let mut text = Vec::new();
+        let mut img_list = Vec::new();
+        parse_novel_text(html, &mut text, &mut img_list, &SiteProfile::default());
+
+        assert_eq!(
+            text,
+            vec![
+                Content::Paragraph(vec![
+                    Inline::Text(String::from("第一段 & ")),
+                    Inline::Bold(vec![Inline::Text(String::from("粗体"))]),
+                    Inline::Italic(vec![Inline::Text(String::from("斜体"))]),
+                ]),
+                Content::Paragraph(vec![
+                    Inline::Ruby {
+                        base: String::from("漢字"),
+                        annotation: String::from("かんじ"),
+                    },
+                    Inline::Link {
+                        href: String::from("/novel/1.html"),
+                        children: vec![Inline::Text(String::from("链接"))],
+                    },
+                ]),
+                Content::LineBreak,
+                Content::LineBreak,
+                Content::Heading {
+                    level: 3,
+                    children: vec![Inline::Text(String::from("小标题"))],
+                },
+                Content::Separator,
+                Content::Image(String::from("https://img.example.com/1.jpg")),
+                Content::Block(vec![Inline::Text(String::from("没有class的div"))]),
+            ]
+        );
+        assert_eq!(
+            img_list,
+            vec![String::from("https://img.example.com/1.jpg")]
+        );
+    }
+
+    #[test]
+    fn test_parse_inline_whitespace() {
+        let (text, _) = chapter(
+            r#"<div id="acontent">
+            <p>
+                foo <b>bar</b> baz
+            </p>
+            <p><i> Hello </i>,
+                <a href="/a.html">my  <b>dear</b></a>	world<br>
+                next   line <span>end</span>　</p>
+        </div>"#,
+        );
+        assert_eq!(
+            text,
+            vec![
+                Content::Paragraph(vec![
+                    plain("foo "),
+                    Inline::Bold(vec![plain("bar")]),
+                    plain(" baz"),
+                ]),
+                Content::Paragraph(vec![
+                    Inline::Italic(vec![plain("Hello ")]),
+                    plain(", "),
+                    Inline::Link {
+                        href: String::from("/a.html"),
+                        children: vec![plain("my "), Inline::Bold(vec![plain("dear")])],
+                    },
+                    plain(" world"),
+                    Inline::LineBreak,
+                    plain("next line "),
+                    plain("end"),
+                ]),
+            ]
+        );
     }
 }
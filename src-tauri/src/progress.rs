@@ -0,0 +1,248 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{Arc, LazyLock},
+};
+
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+
+use crate::model::App;
+
+/// 下载过程中的进度事件
+///
+/// 图形界面中作为 `download-event` 事件发送，命令行中由 [`EventSink`] 绘制进度条或输出json。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+#[cfg_attr(feature = "gui", derive(specta::Type, tauri_specta::Event))]
+pub enum DownloadEvent {
+    /// 开始下载一卷，`chapters`为目录中的章节数
+    VolumeStarted {
+        book_id: String,
+        volume_no: u32,
+        title: String,
+        chapters: u32,
+    },
+    /// 第`i`章下载完成（包括从断点读取的章节），从1开始计数
+    ChapterFetched {
+        i: u32,
+        n: u32,
+        title: String,
+    },
+    /// 获取到一个页面
+    PageFetched {
+        url: String,
+    },
+    /// 第`i`张插图下载完成，从1开始计数
+    ImageFetched {
+        i: u32,
+        n: u32,
+    },
+    /// 请求失败，等待`delay`毫秒后第`attempt`次重试，最多重试`retries`次
+    Retrying {
+        reason: String,
+        attempt: u32,
+        retries: u32,
+        delay: u64,
+    },
+    VolumeSaved {
+        path: String,
+    },
+    /// 一卷下载失败
+    Failed {
+        volume_no: u32,
+        error: String,
+    },
+}
+
+/// 接收进度事件和日志
+pub trait EventSink {
+    fn event(&self, event: &DownloadEvent);
+
+    /// 输出一行日志
+    fn message(&self, msg: &str) {
+        println!("{}", msg);
+    }
+}
+
+/// 命令行的输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressStyle {
+    /// 在日志下方绘制进度条
+    #[default]
+    Bar,
+    /// 每个事件输出一行json，日志输出到标准错误
+    Json,
+}
+
+/// 绘制章节和插图的进度条，日志输出前清除进度条，之后重新绘制
+///
+/// 标准输出不是终端时（例如重定向到文件）不使用控制字符，每次进度更新输出一行。
+pub struct BarSink {
+    line: Mutex<Option<String>>,
+    tty: bool,
+}
+
+impl Default for BarSink {
+    fn default() -> Self {
+        Self {
+            line: Mutex::new(None),
+            tty: io::stdout().is_terminal(),
+        }
+    }
+}
+
+impl BarSink {
+    const WIDTH: u32 = 30;
+
+    fn draw(&self, label: &str, i: u32, n: u32) {
+        let n = n.max(1);
+        let filled = (i.min(n) * Self::WIDTH / n) as usize;
+        let line = format!(
+            "  {} [{}{}] {}/{}",
+            label,
+            "#".repeat(filled),
+            "-".repeat(Self::WIDTH as usize - filled),
+            i,
+            n
+        );
+        let mut current = self.line.lock();
+        let mut stdout = io::stdout().lock();
+        if !self.tty {
+            let _ = writeln!(stdout, "{}", line);
+            return;
+        }
+        let _ = write!(stdout, "\r\x1b[K{}", line);
+        if i >= n {
+            // 完成后换行，保留最终进度
+            let _ = writeln!(stdout);
+            *current = None;
+        } else {
+            *current = Some(line);
+        }
+        let _ = stdout.flush();
+    }
+}
+
+impl EventSink for BarSink {
+    fn event(&self, event: &DownloadEvent) {
+        match event {
+            DownloadEvent::ChapterFetched { i, n, .. } => self.draw("章节", *i, *n),
+            DownloadEvent::ImageFetched { i, n } => self.draw("插图", *i, *n),
+            DownloadEvent::Retrying {
+                reason,
+                attempt,
+                retries,
+                delay,
+            } => self.message(&format!(
+                "{}，{:.1}秒后重试({}/{})....",
+                reason,
+                *delay as f64 / 1000.0,
+                attempt,
+                retries
+            )),
+            _ => (),
+        }
+    }
+
+    fn message(&self, msg: &str) {
+        let line = self.line.lock();
+        let mut stdout = io::stdout().lock();
+        if line.is_some() {
+            let _ = write!(stdout, "\r\x1b[K");
+        }
+        let _ = writeln!(stdout, "{}", msg);
+        if let Some(line) = line.as_ref() {
+            let _ = write!(stdout, "{}", line);
+        }
+        let _ = stdout.flush();
+    }
+}
+
+/// 标准输出只包含事件，方便脚本解析
+pub struct JsonSink;
+
+impl EventSink for JsonSink {
+    fn event(&self, event: &DownloadEvent) {
+        if let Ok(json) = serde_json::to_string(event) {
+            println!("{}", json);
+        }
+    }
+
+    fn message(&self, msg: &str) {
+        eprintln!("{}", msg);
+    }
+}
+
+type SharedSink = Arc<dyn EventSink + Send + Sync>;
+
+/// 命令行指定的事件接收器
+static SINK: RwLock<Option<SharedSink>> = RwLock::new(None);
+
+static DEFAULT_SINK: LazyLock<SharedSink> = LazyLock::new(|| Arc::new(BarSink::default()));
+
+/// 设置命令行使用的事件接收器，没有设置时绘制进度条
+pub fn set_sink(sink: SharedSink) {
+    *SINK.write() = Some(sink);
+}
+
+pub fn set_style(style: ProgressStyle) {
+    match style {
+        ProgressStyle::Bar => set_sink(Arc::new(BarSink::default())),
+        ProgressStyle::Json => set_sink(Arc::new(JsonSink)),
+    }
+}
+
+pub(crate) fn sink() -> SharedSink {
+    SINK.read().clone().unwrap_or_else(|| DEFAULT_SINK.clone())
+}
+
+/// 发送进度事件
+///
+/// 图形界面中发送给前端，只有命令行指定了输出格式时才同时输出到终端。
+pub fn emit(_app: Option<&App>, event: DownloadEvent) {
+    #[cfg(feature = "gui")]
+    {
+        if let Some(app) = _app {
+            crate::event::TauriSink(app).event(&event);
+            let sink = SINK.read().clone();
+            if let Some(sink) = sink {
+                sink.event(&event);
+            }
+            return;
+        }
+    }
+
+    sink().event(&event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let event = DownloadEvent::ChapterFetched {
+            i: 1,
+            n: 2,
+            title: String::from("第一章"),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"kind":"chapterFetched","i":1,"n":2,"title":"第一章"}"#
+        );
+        let event = DownloadEvent::VolumeStarted {
+            book_id: String::from("1"),
+            volume_no: 2,
+            title: String::from("第二卷"),
+            chapters: 10,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"kind":"volumeStarted","bookId":"1","volumeNo":2,"title":"第二卷","chapters":10}"#
+        );
+    }
+}
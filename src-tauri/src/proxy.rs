@@ -0,0 +1,75 @@
+use reqwest::{NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// 代理设置，支持 http、https 和 socks5
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct ProxyConfig {
+    /// 代理地址，例如 `http://127.0.0.1:7890` 或 `socks5://127.0.0.1:1080`，为空时不使用代理
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    /// 不使用代理的域名
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// 图片请求使用的代理，为空时与页面使用同一个代理
+    #[serde(default)]
+    pub image_url: String,
+}
+
+impl ProxyConfig {
+    /// 页面和更新检测使用的代理
+    pub fn page_proxy(&self) -> Result<Option<Proxy>> {
+        self.build(&self.url)
+    }
+
+    /// 图片使用的代理
+    pub fn image_proxy(&self) -> Result<Option<Proxy>> {
+        if self.image_url.trim().is_empty() {
+            self.page_proxy()
+        } else {
+            self.build(&self.image_url)
+        }
+    }
+
+    fn build(&self, url: &str) -> Result<Option<Proxy>> {
+        let url = url.trim();
+        if url.is_empty() {
+            return Ok(None);
+        }
+        let mut proxy = Proxy::all(url)?;
+        if !self.username.is_empty() {
+            proxy = proxy.basic_auth(&self.username, &self.password);
+        }
+        if !self.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(NoProxy::from_string(&self.no_proxy.join(",")));
+        }
+        Ok(Some(proxy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_proxy() {
+        let mut config = ProxyConfig::default();
+        assert!(config.page_proxy().unwrap().is_none());
+        assert!(config.image_proxy().unwrap().is_none());
+
+        config.url = String::from("socks5://127.0.0.1:1080");
+        config.no_proxy = vec![String::from("localhost")];
+        assert!(config.page_proxy().unwrap().is_some());
+        assert!(config.image_proxy().unwrap().is_some());
+
+        config.url = String::from("not a url");
+        assert!(config.page_proxy().is_err());
+    }
+}
@@ -0,0 +1,511 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bail,
+    config::Config,
+    downloader::{Downloader, DownloaderConfig},
+    error::{Result, SiteError},
+    manage::downloaded_volumes,
+    message::send,
+    model::{App, BookSummary},
+    retry::RetryPolicy,
+    source::BiliSource,
+    utils::parse_volume_list,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct QueueConfig {
+    /// 保存下载队列的文件
+    #[serde(default = "default_file")]
+    pub file: String,
+    /// 下载失败后的重试策略，每次运行队列时重新计数
+    #[serde(default = "default_retry")]
+    pub retry: RetryPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            file: default_file(),
+            retry: default_retry(),
+        }
+    }
+}
+
+fn default_file() -> String {
+    String::from("./queue.json")
+}
+
+fn default_retry() -> RetryPolicy {
+    RetryPolicy::new(3, 30_000, 300_000)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub enum QueueMode {
+    /// 重新下载选中的卷
+    #[default]
+    Download,
+    /// 只下载已有文件中没有的章节
+    Update,
+    /// 把选中的卷合并为一个文件
+    Omnibus,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub enum QueueStatus {
+    #[default]
+    Pending,
+    Running,
+    Done,
+    /// 重试次数用完后仍然失败，下次运行队列时重新下载
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct QueueEntry {
+    pub id: u32,
+    pub book_id: String,
+    /// 书名，开始下载后才会获取
+    #[serde(default)]
+    pub title: Option<String>,
+    /// 要下载的卷，格式与命令行的 `--volume` 相同
+    pub volumes: String,
+    #[serde(default)]
+    pub mode: QueueMode,
+    #[serde(default)]
+    pub status: QueueStatus,
+    /// 本次运行中已经尝试的次数
+    #[serde(default)]
+    pub attempts: u32,
+    /// 最后一次失败的原因
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// 保存在文件中的下载队列
+///
+/// 每次修改都立即写回文件，运行队列时其他进程添加或删除的任务也会生效。
+#[derive(Debug)]
+pub struct DownloadQueue {
+    path: PathBuf,
+    entries: Vec<QueueEntry>,
+}
+
+impl DownloadQueue {
+    /// 读取队列文件，文件不存在时返回空队列
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(_) => Vec::new(),
+        };
+        Ok(Self { path, entries })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[QueueEntry] {
+        &self.entries
+    }
+
+    pub fn add(
+        &mut self,
+        book_id: String,
+        title: Option<String>,
+        volumes: String,
+        mode: QueueMode,
+    ) -> Result<QueueEntry> {
+        // 只检查格式，卷数在开始下载后才知道
+        parse_volume_list(&volumes, 0)?;
+        let entry = QueueEntry {
+            id: self.entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1,
+            book_id,
+            title,
+            volumes,
+            mode,
+            status: QueueStatus::Pending,
+            attempts: 0,
+            error: None,
+        };
+        self.entries.push(entry.clone());
+        self.save()?;
+        Ok(entry)
+    }
+
+    /// 删除任务，返回是否存在
+    pub fn remove(&mut self, id: u32) -> Result<bool> {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        if self.entries.len() == len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// 上次运行被中断的任务重新排队
+    pub fn interrupt(&mut self) -> Result<()> {
+        for entry in &mut self.entries {
+            if entry.status == QueueStatus::Running {
+                entry.status = QueueStatus::Pending;
+            }
+        }
+        self.save()
+    }
+
+    /// 下一个需要下载的任务
+    fn next(&self, max_attempts: u32) -> Option<QueueEntry> {
+        self.entries
+            .iter()
+            .find(|entry| match entry.status {
+                QueueStatus::Pending => true,
+                QueueStatus::Failed => entry.attempts < max_attempts,
+                _ => false,
+            })
+            .cloned()
+    }
+
+    fn update(&mut self, id: u32, f: impl FnOnce(&mut QueueEntry)) -> Result<()> {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id) {
+            f(entry);
+            self.save()?;
+        }
+        Ok(())
+    }
+}
+
+/// 依次下载队列中的任务，返回失败的任务数
+///
+/// 失败的任务按重试策略等待后重新下载，书籍不存在等无法恢复的错误不会重试。
+pub async fn run_queue(config: &Config, app: Option<&App>) -> Result<usize> {
+    let path = &config.queue.file;
+    let policy = &config.queue.retry;
+    let max_attempts = policy.max_attempts.max(1);
+
+    let mut queue = DownloadQueue::load(path)?;
+    for entry in &mut queue.entries {
+        if matches!(entry.status, QueueStatus::Running | QueueStatus::Failed) {
+            entry.status = QueueStatus::Pending;
+        }
+        entry.attempts = 0;
+    }
+    queue.save()?;
+
+    loop {
+        // 重新读取，运行期间添加或删除的任务也会生效
+        let mut queue = DownloadQueue::load(path)?;
+        let Some(entry) = queue.next(max_attempts) else {
+            break;
+        };
+        if entry.attempts > 0 {
+            let delay = policy.delay(entry.attempts);
+            send(app, &format!("{}秒后重试任务{}", delay.as_secs(), entry.id));
+            tokio::time::sleep(delay).await;
+        }
+        queue.update(entry.id, |entry| entry.status = QueueStatus::Running)?;
+        send(
+            app,
+            &format!(
+                "开始任务{}，书籍{}，第{}卷",
+                entry.id, entry.book_id, entry.volumes
+            ),
+        );
+
+        let mut title = entry.title.clone();
+        let result = download_entry(config, app, &entry, &mut title).await;
+
+        let mut queue = DownloadQueue::load(path)?;
+        queue.update(entry.id, |entry| {
+            entry.title = title;
+            entry.attempts += 1;
+            match result {
+                Ok(()) => {
+                    entry.status = QueueStatus::Done;
+                    entry.error = None;
+                }
+                Err(err) => {
+                    send(app, &format!("任务{}失败: {:?}", entry.id, err));
+                    entry.status = QueueStatus::Failed;
+                    entry.error = Some(err.to_string());
+                    if SiteError::find(&err).is_some_and(|e| e.is_permanent()) {
+                        entry.attempts = max_attempts;
+                    }
+                }
+            }
+        })?;
+    }
+
+    let failed = DownloadQueue::load(path)?
+        .entries
+        .iter()
+        .filter(|entry| entry.status == QueueStatus::Failed)
+        .count();
+    send(app, &format!("队列下载完成，{}个任务失败", failed));
+    Ok(failed)
+}
+
+/// 把书籍中书库里还没有的卷加入队列，返回添加的任务和获取失败的书籍
+///
+/// 单本书获取失败时继续处理其余书籍。
+pub async fn queue_missing(
+    config: &Config,
+    books: Vec<BookSummary>,
+    app: Option<&App>,
+) -> Result<(Vec<QueueEntry>, Vec<String>)> {
+    let downloaded = downloaded_volumes(&config.output)?;
+
+    let mut added = Vec::new();
+    let mut failed = Vec::new();
+    for book in books {
+        let result = async {
+            let downloader = Downloader::new(
+                DownloaderConfig::from_config(config, book.id.clone(), app.cloned())?,
+                BiliSource::from_config(config)?,
+            )
+            .await?;
+            Ok::<_, color_eyre::Report>(downloader.missing_volumes(&downloaded))
+        }
+        .await;
+        match result {
+            Ok(missing) if missing.is_empty() => {
+                send(app, &format!("{}的所有卷都已下载，跳过", book.title));
+            }
+            Ok(missing) => {
+                let volumes = missing
+                    .iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let mut queue = DownloadQueue::load(&config.queue.file)?;
+                let entry = queue.add(book.id, Some(book.title), volumes, QueueMode::Download)?;
+                send(
+                    app,
+                    &format!(
+                        "已添加任务{}，{}，第{}卷",
+                        entry.id,
+                        entry.title.as_deref().unwrap_or_default(),
+                        entry.volumes
+                    ),
+                );
+                added.push(entry);
+            }
+            Err(err) => {
+                send(app, &format!("获取{}失败: {:?}", book.title, err));
+                failed.push(format!("{}({})", book.title, book.id));
+            }
+        }
+    }
+    Ok((added, failed))
+}
+
+/// 把书籍中还没有下载的卷加入队列并下载，全部处理完后一起报告失败的书籍和任务
+pub async fn download_missing(
+    config: &Config,
+    books: Vec<BookSummary>,
+    app: Option<&App>,
+) -> Result<()> {
+    let (_, failed_books) = queue_missing(config, books, app).await?;
+    let failed = run_queue(config, app).await?;
+
+    let mut errors = Vec::new();
+    if !failed_books.is_empty() {
+        errors.push(format!(
+            "{}本书获取失败: {}",
+            failed_books.len(),
+            failed_books.join("、")
+        ));
+    }
+    if failed > 0 {
+        errors.push(format!("{}个任务下载失败", failed));
+    }
+    if !errors.is_empty() {
+        bail!("{}", errors.join("；"));
+    }
+    Ok(())
+}
+
+async fn download_entry(
+    config: &Config,
+    app: Option<&App>,
+    entry: &QueueEntry,
+    title: &mut Option<String>,
+) -> Result<()> {
+    let downloader = Downloader::new(
+        DownloaderConfig::from_config(config, entry.book_id.clone(), app.cloned())?,
+        BiliSource::from_config(config)?,
+    )
+    .await?;
+    title.clone_from(&downloader.book_info.title);
+    let list = parse_volume_list(&entry.volumes, downloader.volume_infos.len())?;
+    match entry.mode {
+        QueueMode::Download => downloader.download(list.into_iter()).await,
+        QueueMode::Update => downloader.update(list.into_iter()).await,
+        QueueMode::Omnibus => downloader.download_omnibus(list.into_iter()).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        recorder::{HttpMode, RecorderConfig},
+        runtime::{RUN_MODE, RunMode},
+    };
+
+    #[test]
+    fn test_queue_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("queue.json");
+
+        let mut queue = DownloadQueue::load(&path).unwrap();
+        assert!(queue.entries().is_empty());
+        queue
+            .add(
+                String::from("1"),
+                None,
+                String::from("all"),
+                QueueMode::Download,
+            )
+            .unwrap();
+        let entry = queue
+            .add(
+                String::from("2"),
+                None,
+                String::from("1,3-4"),
+                QueueMode::Update,
+            )
+            .unwrap();
+        assert_eq!(entry.id, 2);
+        assert!(
+            queue
+                .add(
+                    String::from("3"),
+                    None,
+                    String::from("a"),
+                    QueueMode::Download
+                )
+                .is_err()
+        );
+        queue
+            .update(1, |entry| entry.status = QueueStatus::Running)
+            .unwrap();
+
+        // 重新打开后，被中断的任务重新排队
+        let mut queue = DownloadQueue::load(&path).unwrap();
+        assert_eq!(queue.entries().len(), 2);
+        queue.interrupt().unwrap();
+        assert_eq!(queue.entries()[0].status, QueueStatus::Pending);
+        assert!(queue.remove(1).unwrap());
+        assert!(!queue.remove(1).unwrap());
+        assert_eq!(DownloadQueue::load(&path).unwrap().entries(), &[entry]);
+    }
+
+    fn replay_config(dir: &Path) -> Config {
+        Config {
+            output: dir.to_string_lossy().to_string(),
+            checkpoint_dir: dir.join("checkpoint").to_string_lossy().to_string(),
+            recorder: RecorderConfig {
+                mode: HttpMode::Replay,
+                dir: concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/replay").to_string(),
+            },
+            queue: QueueConfig {
+                file: dir.join("queue.json").to_string_lossy().to_string(),
+                retry: RetryPolicy::new(2, 0, 0),
+            },
+            ..Config::default()
+        }
+    }
+
+    fn book(id: &str, title: &str) -> BookSummary {
+        BookSummary {
+            id: id.to_string(),
+            title: title.to_string(),
+            author: None,
+            cover: None,
+            tags: Vec::new(),
+            status: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_queue() {
+        *RUN_MODE.lock() = RunMode::Cli;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let config = replay_config(dir);
+
+        let mut queue = DownloadQueue::load(&config.queue.file).unwrap();
+        queue
+            .add(
+                String::from("9999"),
+                None,
+                String::from("1"),
+                QueueMode::Download,
+            )
+            .unwrap();
+        // 回放目录中没有这本书
+        queue
+            .add(
+                String::from("8888"),
+                None,
+                String::from("all"),
+                QueueMode::Download,
+            )
+            .unwrap();
+
+        assert_eq!(run_queue(&config, None).await.unwrap(), 1);
+        let queue = DownloadQueue::load(&config.queue.file).unwrap();
+        let entries = queue.entries();
+        assert_eq!(entries[0].status, QueueStatus::Done);
+        assert_eq!(entries[0].title.as_deref(), Some("测试小说"));
+        assert!(dir.join("测试小说").join("测试小说-第一卷.epub").exists());
+        assert_eq!(entries[1].status, QueueStatus::Failed);
+        assert!(entries[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_queue_missing() {
+        *RUN_MODE.lock() = RunMode::Cli;
+        let temp = tempfile::tempdir().unwrap();
+        let config = replay_config(temp.path());
+
+        // 一本书获取失败时继续处理其他书
+        let (added, failed) = queue_missing(
+            &config,
+            vec![book("8888", "不存在"), book("9999", "测试小说")],
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].book_id, "9999");
+        assert_eq!(added[0].volumes, "1,2");
+        assert_eq!(failed, vec![String::from("不存在(8888)")]);
+
+        assert_eq!(run_queue(&config, None).await.unwrap(), 0);
+        // 已下载的卷不再加入队列
+        let (added, _) = queue_missing(&config, vec![book("9999", "测试小说")], None)
+            .await
+            .unwrap();
+        assert!(added.is_empty());
+    }
+}
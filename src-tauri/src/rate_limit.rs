@@ -0,0 +1,217 @@
+use std::{collections::HashMap, time::Duration};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use url::Url;
+
+/// 单个站点的限速设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct HostRateLimit {
+    /// 每分钟请求数
+    pub requests_per_minute: f64,
+    /// 允许连续发出的请求数
+    pub burst: u32,
+}
+
+impl HostRateLimit {
+    pub const fn new(requests_per_minute: f64, burst: u32) -> Self {
+        Self {
+            requests_per_minute,
+            burst,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct RateLimitConfig {
+    /// 章节等页面请求的默认限速
+    #[serde(default = "default_page")]
+    pub page: HostRateLimit,
+    /// 图片请求的默认限速
+    #[serde(default = "default_image")]
+    pub image: HostRateLimit,
+    /// 按域名覆盖默认限速
+    #[serde(default)]
+    pub hosts: HashMap<String, HostRateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            page: default_page(),
+            image: default_image(),
+            hosts: HashMap::new(),
+        }
+    }
+}
+
+fn default_page() -> HostRateLimit {
+    HostRateLimit::new(8.0, 3)
+}
+
+fn default_image() -> HostRateLimit {
+    HostRateLimit::new(60.0, 5)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    Page,
+    Image,
+}
+
+/// 触发反爬后速率最多降到原来的 1/16
+const MIN_FACTOR: f64 = 1.0 / 16.0;
+/// 连续成功多少次后恢复一级速率
+const RECOVER_AFTER: u32 = 10;
+
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    /// 每秒补充的令牌数
+    rate: f64,
+    /// 反爬降速系数
+    factor: f64,
+    successes: u32,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(limit: &HostRateLimit) -> Self {
+        let capacity = limit.burst.max(1) as f64;
+        Self {
+            tokens: capacity,
+            capacity,
+            rate: limit.requests_per_minute.max(0.0) / 60.0,
+            factor: 1.0,
+            successes: 0,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate * self.factor).min(self.capacity);
+        self.last = now;
+    }
+
+    /// 取出一个令牌，令牌不足时返回需要等待的时间
+    fn take(&mut self) -> Option<Duration> {
+        // 未设置速率时不限速
+        if self.rate <= 0.0 {
+            return None;
+        }
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (1.0 - self.tokens) / (self.rate * self.factor),
+            ))
+        }
+    }
+}
+
+/// 按域名区分的令牌桶限速器
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(String, RequestKind), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 等待直到可以向该url所在的域名发出请求
+    pub async fn acquire(&self, url: &str, kind: RequestKind) {
+        while let Some(wait) = self.with_bucket(url, kind, Bucket::take) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// 检测到反爬页面时调用，清空令牌并将速率减半
+    pub fn slow_down(&self, url: &str, kind: RequestKind) {
+        self.with_bucket(url, kind, |bucket| {
+            bucket.refill();
+            bucket.tokens = 0.0;
+            bucket.factor = (bucket.factor / 2.0).max(MIN_FACTOR);
+            bucket.successes = 0;
+        });
+    }
+
+    /// 请求成功时调用，连续成功后逐步恢复速率
+    pub fn record_success(&self, url: &str, kind: RequestKind) {
+        self.with_bucket(url, kind, |bucket| {
+            if bucket.factor >= 1.0 {
+                return;
+            }
+            bucket.successes += 1;
+            if bucket.successes >= RECOVER_AFTER {
+                bucket.factor = (bucket.factor * 2.0).min(1.0);
+                bucket.successes = 0;
+            }
+        });
+    }
+
+    fn with_bucket<T>(&self, url: &str, kind: RequestKind, f: impl FnOnce(&mut Bucket) -> T) -> T {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry((host.clone(), kind)).or_insert_with(|| {
+            let limit = self.config.hosts.get(&host).unwrap_or(match kind {
+                RequestKind::Page => &self.config.page,
+                RequestKind::Image => &self.config.image,
+            });
+            Bucket::new(limit)
+        });
+        f(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            page: HostRateLimit::new(6000.0, 2),
+            ..RateLimitConfig::default()
+        });
+        let url = "https://www.bilinovel.com/novel/1.html";
+
+        let start = Instant::now();
+        limiter.acquire(url, RequestKind::Page).await;
+        limiter.acquire(url, RequestKind::Page).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        // 其他域名不受影响
+        limiter
+            .acquire("https://img.example.com/1.jpg", RequestKind::Page)
+            .await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // 每秒100个请求，第三个请求需要等待10毫秒
+        limiter.acquire(url, RequestKind::Page).await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert!(start.elapsed() < Duration::from_millis(11));
+
+        // 降速后每秒50个请求
+        limiter.slow_down(url, RequestKind::Page);
+        let start = Instant::now();
+        limiter.acquire(url, RequestKind::Page).await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(start.elapsed() < Duration::from_millis(21));
+    }
+}
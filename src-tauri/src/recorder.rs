@@ -0,0 +1,142 @@
+use std::{
+    fs::{self, create_dir_all},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{client::HttpResponse, err, error::Result, utils::cache_key};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub enum HttpMode {
+    /// 正常联网
+    #[default]
+    Live,
+    /// 联网并把所有请求和响应写入目录
+    Record,
+    /// 只从目录中读取响应，不联网
+    Replay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct RecorderConfig {
+    #[serde(default)]
+    pub mode: HttpMode,
+    #[serde(default = "default_recorder_dir")]
+    pub dir: String,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            mode: HttpMode::Live,
+            dir: default_recorder_dir(),
+        }
+    }
+}
+
+fn default_recorder_dir() -> String {
+    String::from("./fixtures")
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordMeta {
+    url: String,
+    final_url: String,
+    status: u16,
+}
+
+/// 请求录制与回放
+///
+/// 每个url对应目录下的两个文件：`<key>.json` 保存请求url、最终url和状态码，
+/// `<key>.body` 保存响应内容。
+pub struct HttpRecorder {
+    mode: HttpMode,
+    dir: PathBuf,
+}
+
+impl HttpRecorder {
+    pub fn new(mode: HttpMode, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            mode,
+            dir: dir.into(),
+        }
+    }
+
+    /// 联网模式下返回 `None`
+    pub fn from_config(config: &RecorderConfig) -> Option<Self> {
+        match config.mode {
+            HttpMode::Live => None,
+            mode => Some(Self::new(mode, &config.dir)),
+        }
+    }
+
+    pub fn mode(&self) -> HttpMode {
+        self.mode
+    }
+
+    pub fn load(&self, url: &str) -> Result<HttpResponse> {
+        let key = cache_key(url);
+        let meta = fs::read_to_string(self.dir.join(format!("{}.json", key)))
+            .map_err(|_| err!("回放目录中没有该请求: {}", url))?;
+        let meta: RecordMeta = serde_json::from_str(&meta)?;
+        let body = fs::read(self.dir.join(format!("{}.body", key)))?;
+        Ok(HttpResponse {
+            url: meta.final_url,
+            status: meta.status,
+            body,
+        })
+    }
+
+    pub fn save(&self, url: &str, response: &HttpResponse) -> Result<()> {
+        create_dir_all(&self.dir)?;
+        let key = cache_key(url);
+        let meta = RecordMeta {
+            url: url.to_string(),
+            final_url: response.url.clone(),
+            status: response.status,
+        };
+        fs::write(
+            self.dir.join(format!("{}.json", key)),
+            serde_json::to_string_pretty(&meta)?,
+        )?;
+        fs::write(self.dir.join(format!("{}.body", key)), &response.body)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_then_replay() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let url = "https://www.bilinovel.com/novel/1.html";
+        let response = HttpResponse {
+            url: url.to_string(),
+            status: 200,
+            body: b"<html></html>".to_vec(),
+        };
+        HttpRecorder::new(HttpMode::Record, dir)
+            .save(url, &response)
+            .unwrap();
+
+        let replay = HttpRecorder::new(HttpMode::Replay, dir);
+        let loaded = replay.load(url).unwrap();
+        assert_eq!(loaded.url, url);
+        assert_eq!(loaded.status, 200);
+        assert_eq!(loaded.body, response.body);
+        assert!(
+            replay
+                .load("https://www.bilinovel.com/novel/2.html")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct RedirectConfig {
+    /// 可以互相重定向的等价域名，例如手机版、繁体版和镜像站
+    #[serde(default = "default_allowed_hosts")]
+    pub allowed_hosts: Vec<String>,
+    /// 重定向目标包含这些内容时视为登录或验证页面
+    #[serde(default = "default_blocked_patterns")]
+    pub blocked_patterns: Vec<String>,
+}
+
+impl Default for RedirectConfig {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: default_allowed_hosts(),
+            blocked_patterns: default_blocked_patterns(),
+        }
+    }
+}
+
+fn default_allowed_hosts() -> Vec<String> {
+    [
+        "www.bilinovel.com",
+        "tw.bilinovel.com",
+        "bilinovel.com",
+        "www.linovelib.com",
+        "tw.linovelib.com",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_blocked_patterns() -> Vec<String> {
+    [
+        "login",
+        "/user/",
+        "register",
+        "cdn-cgi/challenge",
+        "captcha",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+impl RedirectConfig {
+    /// 是否可以跟随从`from`到`to`的重定向
+    ///
+    /// 同一域名或白名单内域名之间的重定向可以跟随，登录和验证页面除外。
+    /// 书籍目录（例如`/novel/1/`）下的章节页只能跳转到同一目录，搜索等其他页面不限制路径。
+    pub fn allows(&self, from: &Url, to: &Url) -> bool {
+        if !matches!(to.scheme(), "http" | "https") {
+            return false;
+        }
+        let target = to.as_str().to_lowercase();
+        if self
+            .blocked_patterns
+            .iter()
+            .any(|pattern| target.contains(&pattern.to_lowercase()))
+        {
+            return false;
+        }
+        let same_site = match (from.host_str(), to.host_str()) {
+            (Some(from), Some(to)) => from == to || (self.is_allowed(from) && self.is_allowed(to)),
+            _ => false,
+        };
+        same_site && same_page(from.path(), to.path())
+    }
+
+    fn is_allowed(&self, host: &str) -> bool {
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+}
+
+/// 章节页的路径不变，或者仍在同一本书的目录下，例如分页`_0`的变化
+///
+/// 章节页跳转到书籍首页等其他页面时，按章节解析只会得到误导性的错误。
+fn same_page(from: &str, to: &str) -> bool {
+    let dir = |path: &str| path.rfind('/').map(|i| path[..=i].to_string());
+    match dir(from) {
+        // `/novel/{id}/`以下才是章节页
+        Some(from_dir) if from_dir.matches('/').count() >= 3 => {
+            from == to || dir(to).is_some_and(|to_dir| to_dir == from_dir)
+        }
+        _ => true,
+    }
+}
+
+/// 只是换了协议或域名的重定向，返回新旧站点地址，之后的请求可以直接改写到新站点
+pub fn moved_origin(from: &Url, to: &Url) -> Option<(String, String)> {
+    if from.path() != to.path() || from.query() != to.query() {
+        return None;
+    }
+    let from = from.origin().ascii_serialization();
+    let to = to.origin().ascii_serialization();
+    (from != to).then_some((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_redirect_policy() {
+        let config = RedirectConfig::default();
+        let from = url("http://www.bilinovel.com/novel/1/2.html");
+
+        assert!(config.allows(&from, &url("https://www.bilinovel.com/novel/1/2.html")));
+        assert!(config.allows(&from, &url("https://tw.bilinovel.com/novel/1/2.html")));
+        assert!(config.allows(&from, &url("https://www.bilinovel.com/novel/1/2_0.html")));
+        // 章节页跳转到书籍首页或其他书
+        assert!(!config.allows(&from, &url("https://www.bilinovel.com/novel/1.html")));
+        assert!(!config.allows(&from, &url("https://www.bilinovel.com/novel/2/2.html")));
+        assert!(!config.allows(&from, &url("https://www.bilinovel.com/")));
+        // 搜索结果只有一本书时跳转到书籍页
+        let search = url("https://www.bilinovel.com/search.html?searchkey=1");
+        assert!(config.allows(&search, &url("https://www.bilinovel.com/novel/1.html")));
+        assert!(!config.allows(&from, &url("https://www.bilinovel.com/login.php?jumpurl=1")));
+        assert!(!config.allows(&from, &url("https://example.com/novel/1/2.html")));
+
+        assert_eq!(
+            moved_origin(&from, &url("https://tw.bilinovel.com/novel/1/2.html")),
+            Some((
+                String::from("http://www.bilinovel.com"),
+                String::from("https://tw.bilinovel.com")
+            ))
+        );
+        assert_eq!(
+            moved_origin(&from, &url("http://www.bilinovel.com/novel/1.html")),
+            None
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::{HashMap, hash_map::RandomState},
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bail,
+    error::{Result, SiteError},
+    model::App,
+    progress::{DownloadEvent, emit},
+};
+
+/// 单类失败的重试策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct RetryPolicy {
+    /// 最多尝试次数（包括第一次）
+    pub max_attempts: u32,
+    /// 第一次重试前的等待时间（毫秒），之后每次翻倍
+    pub base_delay: u64,
+    /// 等待时间上限（毫秒）
+    pub max_delay: u64,
+    /// 随机抖动比例，0.2 表示在 ±20% 范围内浮动
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    pub const fn new(max_attempts: u32, base_delay: u64, max_delay: u64) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            max_delay,
+            jitter: 0.2,
+        }
+    }
+
+    /// 第`attempt`次失败后的等待时间，从1开始计数
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31);
+        let delay = self.base_delay.saturating_mul(1 << exp).min(self.max_delay) as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0) * (random() * 2.0 - 1.0);
+        Duration::from_millis((delay * (1.0 + jitter)).max(0.0) as u64)
+    }
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+/// 返回 [0, 1) 之间的随机数
+fn random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureClass {
+    /// 网络错误
+    Network,
+    /// 触发 Cloudflare 等反爬限制
+    AntiBot,
+    /// 站点暂时无法返回完整内容
+    Unavailable,
+    /// 图片下载失败
+    Image,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "gui", derive(specta::Type))]
+pub struct RetryConfig {
+    #[serde(default = "default_network")]
+    pub network: RetryPolicy,
+    #[serde(default = "default_anti_bot")]
+    pub anti_bot: RetryPolicy,
+    #[serde(default = "default_unavailable")]
+    pub unavailable: RetryPolicy,
+    #[serde(default = "default_image")]
+    pub image: RetryPolicy,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            network: default_network(),
+            anti_bot: default_anti_bot(),
+            unavailable: default_unavailable(),
+            image: default_image(),
+        }
+    }
+}
+
+impl FailureClass {
+    /// 放弃重试时返回的错误
+    fn give_up(self, message: String) -> SiteError {
+        match self {
+            Self::AntiBot | Self::Unavailable => SiteError::blocked(message),
+            Self::Network | Self::Image => SiteError::network(message),
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn policy(&self, class: FailureClass) -> &RetryPolicy {
+        match class {
+            FailureClass::Network => &self.network,
+            FailureClass::AntiBot => &self.anti_bot,
+            FailureClass::Unavailable => &self.unavailable,
+            FailureClass::Image => &self.image,
+        }
+    }
+}
+
+fn default_network() -> RetryPolicy {
+    RetryPolicy::new(5, 3_000, 60_000)
+}
+
+fn default_anti_bot() -> RetryPolicy {
+    RetryPolicy::new(6, 10_000, 300_000)
+}
+
+fn default_unavailable() -> RetryPolicy {
+    RetryPolicy::new(5, 10_000, 120_000)
+}
+
+fn default_image() -> RetryPolicy {
+    RetryPolicy::new(8, 2_000, 60_000)
+}
+
+/// 记录一次请求过程中各类失败的次数
+pub struct Retrier<'a> {
+    config: &'a RetryConfig,
+    attempts: HashMap<FailureClass, u32>,
+}
+
+impl<'a> Retrier<'a> {
+    pub fn new(config: &'a RetryConfig) -> Self {
+        Self {
+            config,
+            attempts: HashMap::new(),
+        }
+    }
+
+    /// 记录一次失败
+    ///
+    /// 未超过该类失败的尝试次数时等待退避时间，否则返回错误。
+    pub async fn wait(
+        &mut self,
+        class: FailureClass,
+        reason: &str,
+        message: Option<&App>,
+    ) -> Result<()> {
+        let policy = self.config.policy(class);
+        let attempt = self.attempts.entry(class).or_insert(0);
+        *attempt += 1;
+        if *attempt >= policy.max_attempts {
+            bail!(class.give_up(format!("{}，已尝试{}次，放弃重试", reason, attempt)));
+        }
+        let delay = policy.delay(*attempt);
+        emit(
+            message,
+            DownloadEvent::Retrying {
+                reason: reason.to_string(),
+                attempt: *attempt,
+                retries: policy.max_attempts - 1,
+                delay: delay.as_millis() as u64,
+            },
+        );
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::new(5, 1_000, 5_000)
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(1_000));
+        assert_eq!(policy.delay(2), Duration::from_millis(2_000));
+        assert_eq!(policy.delay(3), Duration::from_millis(4_000));
+        assert_eq!(policy.delay(4), Duration::from_millis(5_000));
+        assert_eq!(policy.delay(100), Duration::from_millis(5_000));
+
+        let policy = RetryPolicy::new(5, 1_000, 5_000);
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(800) && delay <= Duration::from_millis(1_200));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrier_gives_up() {
+        let config = RetryConfig {
+            network: RetryPolicy::new(3, 0, 0),
+            ..RetryConfig::default()
+        };
+        let mut retrier = Retrier::new(&config);
+        assert!(
+            retrier
+                .wait(FailureClass::Network, "请求失败", None)
+                .await
+                .is_ok()
+        );
+        assert!(
+            retrier
+                .wait(FailureClass::Network, "请求失败", None)
+                .await
+                .is_ok()
+        );
+        let err = retrier
+            .wait(FailureClass::Network, "请求失败", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            SiteError::find(&err),
+            Some(SiteError::Network { .. })
+        ));
+    }
+}
@@ -0,0 +1,257 @@
+use std::{fs, path::Path, sync::LazyLock};
+
+use regex::Regex;
+use scraper::Selector;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{bail, err, error::Result};
+
+/// 支持的站点配置版本
+pub const PROFILE_VERSION: u64 = 1;
+
+const DEFAULT_PROFILE: &str = include_str!("../profiles/bilinovel.json");
+
+static DEFAULT_PATTERNS: LazyLock<CompiledPatterns> = LazyLock::new(|| {
+    let profile: Value = serde_json::from_str(DEFAULT_PROFILE).expect("内置站点配置无效");
+    let patterns: Patterns =
+        serde_json::from_value(profile["patterns"].clone()).expect("内置站点配置无效");
+    CompiledPatterns::new(&patterns).expect("内置站点配置无效")
+});
+
+/// 站点配置
+///
+/// 页面解析用到的css选择器、正则和判断页面状态的标记文字。站点改版时可以在本地配置文件中修改，
+/// 文件中只需写需要修改的字段，其余使用内置配置。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteProfile {
+    pub version: u64,
+    pub selectors: Selectors,
+    pub patterns: Patterns,
+    pub markers: Markers,
+    /// 加载时编译好的 `patterns`
+    #[serde(skip)]
+    compiled: CompiledPatterns,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Selectors {
+    pub book_title: String,
+    pub author: String,
+    pub illustrator: String,
+    pub cover: String,
+    pub publisher: String,
+    pub tag: String,
+    pub status: String,
+    pub description: String,
+    pub alias: String,
+    pub book_meta: String,
+    pub last_update: String,
+    /// 目录页中的一卷
+    pub volume: String,
+    pub volume_title: String,
+    pub volume_cover: String,
+    pub chapter: String,
+    /// 章节正文
+    pub content: String,
+    /// 搜索结果和排行榜中的一本书
+    pub book_item: String,
+    pub book_item_title: String,
+    pub book_item_author: String,
+    pub book_item_tag: String,
+    pub page_link: String,
+    pub canonical: String,
+    /// 正文中的注释，有 `title` 属性时 `title` 为注释内容
+    pub note: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Patterns {
+    /// 第一个分组为下一页的链接
+    pub next_url: String,
+    pub chapterlog_version: String,
+    /// 第一个分组为书籍id
+    pub book_id: String,
+    /// 正文中括号内的译注，第一个分组为注释内容
+    pub inline_note: String,
+}
+
+/// 编译后的 [`Patterns`]，避免每次解析页面时重新编译
+#[derive(Debug, Clone)]
+pub struct CompiledPatterns {
+    pub next_url: Regex,
+    pub chapterlog_version: Regex,
+    pub book_id: Regex,
+    pub inline_note: Regex,
+}
+
+impl CompiledPatterns {
+    fn new(patterns: &Patterns) -> Result<Self> {
+        let compile = |name: &str, pattern: &str| {
+            Regex::new(pattern).map_err(|e| err!("正则 {} 无效: {}", name, e))
+        };
+        Ok(Self {
+            next_url: compile("nextUrl", &patterns.next_url)?,
+            chapterlog_version: compile("chapterlogVersion", &patterns.chapterlog_version)?,
+            book_id: compile("bookId", &patterns.book_id)?,
+            inline_note: compile("inlineNote", &patterns.inline_note)?,
+        })
+    }
+}
+
+impl Default for CompiledPatterns {
+    fn default() -> Self {
+        DEFAULT_PATTERNS.clone()
+    }
+}
+
+/// 页面中出现这些文字时判断为对应的状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Markers {
+    pub anti_bot: Vec<String>,
+    pub blocked: Vec<String>,
+    pub deleted: Vec<String>,
+    pub not_reviewed: Vec<String>,
+    pub not_found: Vec<String>,
+    pub unavailable: Vec<String>,
+    /// 使用了混淆字体
+    pub font_obfuscation: Vec<String>,
+    /// 正文中包含这些文字的块会被跳过
+    pub skip_text: Vec<String>,
+    /// 连载状态
+    pub status: Vec<String>,
+}
+
+impl Default for SiteProfile {
+    fn default() -> Self {
+        serde_json::from_str(DEFAULT_PROFILE).expect("内置站点配置无效")
+    }
+}
+
+impl SiteProfile {
+    /// 读取站点配置文件，路径为空或文件不存在时使用内置配置
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if path.as_os_str().is_empty() || !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Self::from_json(&content).map_err(|e| err!("站点配置 {} 无效: {}", path.display(), e))
+    }
+
+    /// 解析json配置，缺少的字段使用内置配置
+    pub fn from_json(json: &str) -> Result<Self> {
+        let custom: Value = serde_json::from_str(json)?;
+        if let Some(version) = custom.get("version").and_then(Value::as_u64)
+            && version > PROFILE_VERSION
+        {
+            bail!(
+                "配置版本为{}，当前只支持到{}，请更新程序",
+                version,
+                PROFILE_VERSION
+            );
+        }
+        let mut profile: Value = serde_json::from_str(DEFAULT_PROFILE)?;
+        merge(&mut profile, custom);
+        let mut profile: SiteProfile = serde_json::from_value(profile)?;
+        profile.validate()?;
+        profile.compiled = CompiledPatterns::new(&profile.patterns)?;
+        Ok(profile)
+    }
+
+    /// 检查所有选择器都可以解析
+    fn validate(&self) -> Result<()> {
+        for (name, selector) in string_fields(&self.selectors)? {
+            if let Err(e) = Selector::parse(&selector) {
+                bail!("选择器 {} 无效: {}", name, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 编译好的正则
+    pub fn regex(&self) -> &CompiledPatterns {
+        &self.compiled
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Markers {
+    /// `text` 是否包含 `markers` 中的任意一个
+    pub fn matches(markers: &[String], text: &str) -> bool {
+        markers.iter().any(|marker| text.contains(marker.as_str()))
+    }
+}
+
+/// 解析已经检查过的选择器
+pub fn selector(selector: &str) -> Selector {
+    Selector::parse(selector).unwrap()
+}
+
+fn string_fields(value: &impl Serialize) -> Result<Vec<(String, String)>> {
+    let Value::Object(map) = serde_json::to_value(value)? else {
+        return Ok(Vec::new());
+    };
+    Ok(map
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_str()?.to_string())))
+        .collect())
+}
+
+fn merge(base: &mut Value, custom: Value) {
+    match (base, custom) {
+        (Value::Object(base), Value::Object(custom)) => {
+            for (key, value) in custom {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, custom) => *base = custom,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_profile() {
+        let default = SiteProfile::default();
+        default.validate().unwrap();
+
+        let profile = SiteProfile::from_json(
+            r#"{"selectors": {"bookTitle": "h2.title"}, "markers": {"blocked": ["Access denied"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(profile.selectors.book_title, "h2.title");
+        assert_eq!(profile.selectors.author, default.selectors.author);
+        assert_eq!(profile.markers.blocked, vec![String::from("Access denied")]);
+        assert_eq!(profile.markers.deleted, default.markers.deleted);
+
+        let profile =
+            SiteProfile::from_json(r#"{"patterns": {"bookId": "/book/(\\d+)"}}"#).unwrap();
+        assert_eq!(
+            profile.regex().book_id.as_str(),
+            profile.patterns.book_id.as_str()
+        );
+        assert_eq!(
+            profile.regex().next_url.as_str(),
+            default.regex().next_url.as_str()
+        );
+
+        assert!(SiteProfile::from_json(r#"{"selectors": {"content": "div#"}}"#).is_err());
+        assert!(SiteProfile::from_json(r#"{"patterns": {"nextUrl": "("}}"#).is_err());
+        assert!(SiteProfile::from_json(r#"{"version": 99}"#).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::future::Future;
+
+use crate::{
+    error::Result,
+    model::{App, BookInfo, BookList, BookListKind, BookSummary, Content, VolumeInfo},
+};
+
+pub mod bilinovel;
+
+pub use bilinovel::BiliSource;
+
+/// 章节中的一页内容
+pub struct ChapterPage {
+    pub contents: Vec<Content>,
+    /// 本页中出现的图片url
+    pub images: Vec<String>,
+    /// 下一页或下一章的url
+    pub next_url: String,
+    /// `next_url` 是否为本章的下一页
+    pub has_next_page: bool,
+}
+
+/// 小说站点
+///
+/// 下载流程只依赖该 trait，接入新的站点或镜像只需提供一个实现。
+pub trait NovelSource: Send + Sync {
+    fn base_url(&self) -> &str;
+
+    /// 按关键词搜索书籍
+    fn search(
+        &self,
+        keyword: &str,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<Vec<BookSummary>>> + Send;
+
+    /// 获取排行榜或分类列表的第`page`页
+    fn fetch_book_list(
+        &self,
+        kind: BookListKind,
+        page: u32,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<BookList>> + Send;
+
+    /// 获取作者的所有作品，`author`为作者名或作者页链接
+    fn fetch_author_books(
+        &self,
+        author: &str,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<Vec<BookSummary>>> + Send;
+
+    /// 获取书籍信息
+    fn fetch_book_info(
+        &self,
+        book_id: &str,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<BookInfo>> + Send;
+
+    /// 获取卷列表
+    fn fetch_volume_list(
+        &self,
+        book_id: &str,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<Vec<VolumeInfo>>> + Send;
+
+    /// 获取卷详情，把简介、最后更新日期、字数等信息写入`volume`
+    fn fetch_volume_detail(
+        &self,
+        volume: &mut VolumeInfo,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// 获取卷的最后更新时间
+    fn fetch_last_update(
+        &self,
+        book_id: &str,
+        volume_id: &str,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<String>> + Send;
+
+    /// 获取章节中的一页
+    fn fetch_chapter_page(
+        &self,
+        url: &str,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<ChapterPage>> + Send;
+
+    /// 解析第`volume_no`卷第一章的url
+    fn resolve_start_url(
+        &self,
+        volume_infos: &[VolumeInfo],
+        volume_no: usize,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<String>> + Send;
+
+    /// 下载图片
+    fn fetch_image(
+        &self,
+        url: &str,
+        message: Option<&App>,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+}
@@ -0,0 +1,314 @@
+use std::borrow::Cow;
+
+use crate::{
+    bail,
+    cache::PageCache,
+    client::BiliClient,
+    config::Config,
+    err,
+    error::{Result, SiteError},
+    message::send,
+    model::{App, BookInfo, BookList, BookListKind, BookSummary, Content, VolumeInfo},
+    parse::{
+        decode_obfuscated_text, parse_author_books, parse_book_list, parse_last_update,
+        parse_metadata, parse_novel_text, parse_search_result, parse_volume_list,
+        parse_volume_page,
+    },
+    recorder::HttpRecorder,
+    runtime::{RUN_MODE, RunMode},
+    site_profile::SiteProfile,
+    source::{ChapterPage, NovelSource},
+};
+
+/// bilinovel
+pub struct BiliSource {
+    client: BiliClient,
+    base_url: String,
+}
+
+impl BiliSource {
+    pub fn new(client: BiliClient, base_url: &str) -> Self {
+        Self {
+            client,
+            base_url: base_url.to_string(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let client = BiliClient::new(
+            &config.base_url,
+            &config.cookie,
+            &config.user_agent,
+            &config.headers,
+            config.convert_simple_chinese,
+            config.debug,
+        )?
+        .with_proxy(&config.proxy)?
+        .with_cookie_file(&config.cookie_file)?
+        .with_cache(PageCache::from_config(&config.cache))
+        .with_recorder(HttpRecorder::from_config(&config.recorder))
+        .with_retry(config.retry.clone())
+        .with_rate_limit(config.rate_limit.clone())
+        .with_redirect(config.redirect.clone())
+        .with_profile(SiteProfile::load(&config.site_profile)?);
+        Ok(Self::new(client, &config.base_url))
+    }
+
+    pub fn client(&self) -> &BiliClient {
+        &self.client
+    }
+
+    /// `url`为`html`所在页面的链接，用于错误信息
+    fn get_next_url(&self, html: &str, url: &str, message: Option<&App>) -> Result<String> {
+        // 使用正则表达式进行匹配
+        if let Some(captures) = self.client.profile().regex().next_url.captures(html) {
+            // 提取匹配到的第一个分组（即 URL）
+            if let Some(next) = captures.get(1) {
+                return Ok(self.base_url.clone() + next.as_str());
+            }
+        }
+
+        send(message, "寻找章节链接失败");
+        if self.client.is_debug() {
+            send(message, html);
+        }
+        bail!(SiteError::parse(format!("寻找章节链接失败: {}", url)))
+    }
+
+    async fn get_next_chapter_url(&self, url: &str, message: Option<&App>) -> Result<String> {
+        let mut current_url = url.to_string();
+        let mut current_html = self.client.get_html(url, message).await?;
+        loop {
+            let next = self.get_next_url(&current_html, &current_url, message)?;
+            if next.contains("_") {
+                current_html = self.client.get_html(&next, message).await?;
+                current_url = next;
+            } else {
+                return Ok(next);
+            }
+        }
+    }
+
+    fn paragraph_restorer(
+        &self,
+        html: &str,
+        img_list: &mut Vec<String>,
+        url: &str,
+        message: Option<&App>,
+    ) -> Result<Vec<Content>> {
+        let html = match *RUN_MODE.lock() {
+            RunMode::Gui => {
+                #[cfg(feature = "gui")]
+                {
+                    match crate::event::html(message.ok_or(err!("缺少 app handle"))?, html) {
+                        Ok(html) => Cow::Owned(html),
+                        Err(err) => {
+                            if self.client.is_debug() {
+                                send(message, html);
+                            }
+                            bail!(SiteError::parse(format!("章节内容解析失败: {:?}", err)));
+                        }
+                    }
+                }
+
+                #[cfg(not(feature = "gui"))]
+                bail!("当前构建未启用 gui feature");
+            }
+            RunMode::Cli => Cow::Borrowed(html),
+        };
+
+        let mut chapter = Vec::new();
+        parse_novel_text(html.as_ref(), &mut chapter, img_list, self.client.profile());
+
+        if chapter.is_empty() {
+            send(message, "   章节内容为空");
+            if self.client.is_debug() {
+                send(message, &html);
+            }
+            bail!(SiteError::parse(format!("章节内容为空: {}", url)));
+        }
+
+        let chapter = match *RUN_MODE.lock() {
+            RunMode::Gui => chapter,
+            RunMode::Cli => {
+                use crate::paragraph_restorer::ParagraphRestorer;
+                if self.get_chapterlog_version(html.as_ref())? != ParagraphRestorer::get_version() {
+                    bail!(SiteError::parse("章节日志版本不匹配，无法恢复章节顺序"));
+                }
+                let chapter_id = url
+                    .split("/")
+                    .last()
+                    .unwrap()
+                    .split(".")
+                    .next()
+                    .unwrap()
+                    .split("_")
+                    .next()
+                    .unwrap()
+                    .parse::<u64>()
+                    .unwrap();
+
+                let restorer = ParagraphRestorer::new(chapter_id);
+                restorer.restore(chapter)
+            }
+        };
+
+        Ok(chapter)
+    }
+
+    fn get_chapterlog_version(&self, html: &str) -> Result<String> {
+        if let Some(captures) = self
+            .client
+            .profile()
+            .regex()
+            .chapterlog_version
+            .captures(html)
+            && let Some(version) = captures.get(0)
+        {
+            return Ok(version.as_str().to_string());
+        }
+
+        bail!(SiteError::parse("chapterlog.js version not found"))
+    }
+}
+
+impl NovelSource for BiliSource {
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn search(&self, keyword: &str, message: Option<&App>) -> Result<Vec<BookSummary>> {
+        Ok(parse_search_result(
+            &self.client.search(keyword, message).await?,
+            self.client.profile(),
+        ))
+    }
+
+    async fn fetch_book_list(
+        &self,
+        kind: BookListKind,
+        page: u32,
+        message: Option<&App>,
+    ) -> Result<BookList> {
+        let page = page.max(1);
+        Ok(parse_book_list(
+            &self.client.get_book_list(kind, page, message).await?,
+            page,
+            self.client.profile(),
+        ))
+    }
+
+    async fn fetch_author_books(
+        &self,
+        author: &str,
+        message: Option<&App>,
+    ) -> Result<Vec<BookSummary>> {
+        Ok(parse_author_books(
+            &self.client.get_author(author, message).await?,
+            self.client.profile(),
+        ))
+    }
+
+    async fn fetch_book_info(&self, book_id: &str, message: Option<&App>) -> Result<BookInfo> {
+        Ok(parse_metadata(
+            &self.client.get_novel(book_id, message).await?,
+            self.client.profile(),
+        ))
+    }
+
+    async fn fetch_volume_list(
+        &self,
+        book_id: &str,
+        message: Option<&App>,
+    ) -> Result<Vec<VolumeInfo>> {
+        Ok(parse_volume_list(
+            &self.client.get_catalog(book_id, message).await?,
+            self.client.profile(),
+        ))
+    }
+
+    async fn fetch_volume_detail(
+        &self,
+        volume: &mut VolumeInfo,
+        message: Option<&App>,
+    ) -> Result<()> {
+        let url = volume.url_vol.as_ref().ok_or(err!("卷链接为空"))?;
+        let url = if !url.starts_with("http") {
+            format!("{}{}", self.base_url, url)
+        } else {
+            url.to_string()
+        };
+
+        let html = self.client.get_html(&url, message).await?;
+        parse_volume_page(&html, volume, self.client.profile());
+        Ok(())
+    }
+
+    async fn fetch_last_update(
+        &self,
+        book_id: &str,
+        volume_id: &str,
+        message: Option<&App>,
+    ) -> Result<String> {
+        let html = self.client.get_volume(book_id, volume_id, message).await?;
+        parse_last_update(&html, self.client.profile()).ok_or(err!("last_update is required"))
+    }
+
+    async fn fetch_chapter_page(&self, url: &str, message: Option<&App>) -> Result<ChapterPage> {
+        let html = self.client.get_html(url, message).await?;
+
+        let mut images = Vec::new();
+        let mut contents = self.paragraph_restorer(&html, &mut images, url, message)?;
+
+        // 文本解密
+        decode_obfuscated_text(&html, &mut contents, self.client.profile());
+
+        let next_url = self.get_next_url(&html, url, message)?;
+        let has_next_page = next_url.contains("_");
+        Ok(ChapterPage {
+            contents,
+            images,
+            next_url,
+            has_next_page,
+        })
+    }
+
+    async fn resolve_start_url(
+        &self,
+        volume_infos: &[VolumeInfo],
+        volume_no: usize,
+        message: Option<&App>,
+    ) -> Result<String> {
+        let volume = &volume_infos[volume_no - 1];
+        let mut next_url = self.base_url.clone() + &volume.chapter_path_list[0];
+        if next_url.contains("javascript") {
+            let pre_volume = &volume_infos[volume_no - 2];
+            let pre_url_path = pre_volume.chapter_path_list.last().unwrap();
+            let url = self.base_url.clone() + pre_url_path;
+            next_url = self.get_next_chapter_url(&url, message).await?;
+        }
+        Ok(next_url)
+    }
+
+    async fn fetch_image(&self, url: &str, message: Option<&App>) -> Result<Vec<u8>> {
+        self.client.get_img_bytes(url, message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_get_chapterlog_version() {
+        let source = BiliSource::from_config(&Config::default()).unwrap();
+        let html = source
+            .client()
+            .get("https://www.bilinovel.com/novel/1/108523.html")
+            .await
+            .unwrap();
+        let version = source.get_chapterlog_version(&html).unwrap();
+        println!("version: {}", version);
+    }
+}
@@ -4,7 +4,7 @@ use fast2s::convert;
 use image::{ImageFormat, ImageReader, codecs::jpeg::JpegEncoder};
 use semver::Version;
 
-use crate::error::Result;
+use crate::{bail, error::Result};
 
 pub fn t2s(str: &str) -> String {
     // traditional_to_simplified(str).to_string()
@@ -20,13 +20,6 @@ pub fn remove_invalid_chars(filename: &str) -> String {
         .collect()
 }
 
-pub fn escape_epub_text(input: &str) -> String {
-    input
-        .replace("&", "&amp;")
-        .replace("<", "&lt;")
-        .replace(">", "&gt;")
-}
-
 /// 将非png和jpg的图片转为jpg
 pub fn img_to_jpg(data: Vec<u8>) -> Result<Vec<u8>> {
     let format = image::guess_format(&data)?;
@@ -46,6 +39,28 @@ pub fn img_to_jpg(data: Vec<u8>) -> Result<Vec<u8>> {
     Ok(img)
 }
 
+/// 将url转换为可用作文件名的缓存键
+pub fn cache_key(url: &str) -> String {
+    // FNV-1a，保证不同url不会因字符替换而冲突
+    let hash = url.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    let name: String = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(80)
+        .collect();
+    format!("{}-{:016x}", name, hash)
+}
+
 pub fn is_newer_version(local: &str, remote: &str) -> bool {
     let local_ver = Version::parse(local).unwrap_or_else(|_| Version::new(0, 0, 0));
     let remote_ver =
@@ -53,6 +68,32 @@ pub fn is_newer_version(local: &str, remote: &str) -> bool {
     remote_ver > local_ver
 }
 
+/// 解析要下载的卷，`all`表示全部，多卷使用,分隔，连续的多卷使用连字符-，例如`1,3-5`
+pub fn parse_volume_list(spec: &str, total: usize) -> Result<Vec<u32>> {
+    let spec = spec.trim();
+    if spec == "all" {
+        return Ok((1..=total as u32).collect());
+    }
+    let parse = |s: &str| match s.trim().parse::<u32>() {
+        Ok(no) if no > 0 => Ok(no),
+        _ => bail!("卷数 {} 无效", s),
+    };
+    let mut list = Vec::new();
+    for item in spec.split(',') {
+        match item.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    bail!("卷数范围 {} 无效", item);
+                }
+                list.extend(start..=end);
+            }
+            None => list.push(parse(item)?),
+        }
+    }
+    Ok(list)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,4 +103,30 @@ mod tests {
         println!("{}", t2s("妳"));
         println!("{}", convert("妳"));
     }
+
+    #[test]
+    fn test_parse_volume_list() {
+        assert_eq!(parse_volume_list("all", 3).unwrap(), vec![1, 2, 3]);
+        assert_eq!(parse_volume_list("2", 3).unwrap(), vec![2]);
+        assert_eq!(parse_volume_list("1,3-5", 3).unwrap(), vec![1, 3, 4, 5]);
+        assert!(parse_volume_list("", 3).is_err());
+        assert!(parse_volume_list("0", 3).is_err());
+        assert!(parse_volume_list("3-1", 3).is_err());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(
+            cache_key("https://www.bilinovel.com/novel/1/2_2.html"),
+            cache_key("https://www.bilinovel.com/novel/1/2_2.html")
+        );
+        assert_ne!(
+            cache_key("https://www.bilinovel.com/novel/1/2_2.html"),
+            cache_key("https://www.bilinovel.com/novel/1_2/2.html")
+        );
+        assert!(
+            cache_key("https://www.bilinovel.com/novel/1/catalog")
+                .starts_with("www.bilinovel.com_novel_1_catalog-")
+        );
+    }
 }
@@ -1,11 +1,20 @@
 // This file has been generated by Tauri Specta. Do not edit this file manually.
 
 import { invoke as __TAURI_INVOKE } from "@tauri-apps/api/core";
+import { listen as __TAURI_LISTEN, type EventCallback } from "@tauri-apps/api/event";
 
 /** Commands */
 export const commands = {
 	getBookInfo: (bookId: string) => __TAURI_INVOKE<[BookInfo, VolumeInfo[]]>("get_book_info", { bookId }),
-	download: (bookId: string, bookInfo: BookInfo, volumeList: VolumeInfo[], volumeNoList: number[]) => __TAURI_INVOKE<null>("download", { bookId, bookInfo, volumeList, volumeNoList }),
+	searchBooks: (keyword: string) => __TAURI_INVOKE<BookSummary[]>("search_books", { keyword }),
+	browseBooks: (kind: BookListKind, page: number) => __TAURI_INVOKE<BookList>("browse_books", { kind, page }),
+	download: (bookId: string, bookInfo: BookInfo, volumeList: VolumeInfo[], volumeNoList: number[], update: boolean, omnibus: boolean) => __TAURI_INVOKE<null>("download", { bookId, bookInfo, volumeList, volumeNoList, update, omnibus }),
+	authorBooks: (author: string) => __TAURI_INVOKE<BookSummary[]>("author_books", { author }),
+	downloadAuthor: (author: string) => __TAURI_INVOKE<null>("download_author", { author }),
+	queueList: () => __TAURI_INVOKE<QueueEntry[]>("queue_list"),
+	queueAdd: (bookId: string, title: string | null, volumeNoList: number[], mode: QueueMode) => __TAURI_INVOKE<QueueEntry>("queue_add", { bookId, title, volumeNoList, mode }),
+	queueRemove: (id: number) => __TAURI_INVOKE<null>("queue_remove", { id }),
+	queueRun: () => __TAURI_INVOKE<number>("queue_run"),
 	cancelDownload: () => __TAURI_INVOKE<null>("cancel_download"),
 	browserUrl: (url: string) => __TAURI_INVOKE<string>("browser_url", { url }),
 	saveConfig: (newConfig: Config) => __TAURI_INVOKE<null>("save_config", { newConfig }),
@@ -16,6 +25,13 @@ export const commands = {
 	createIndex: () => __TAURI_INVOKE<null>("create_index"),
 };
 
+/** Events */
+export const events = {
+	downloadEvent: {
+		listen: (cb: EventCallback<DownloadEvent>) => __TAURI_LISTEN<DownloadEvent>("download-event", cb),
+	},
+};
+
 /* Types */
 export type Book = {
 	id: string,
@@ -34,25 +50,151 @@ export type BookInfo = {
 	tags: string[],
 	description: string | null,
 	cover: string | null,
+	status?: string | null,
+	word_count?: number | null,
+	last_update?: string | null,
+	illustrator?: string | null,
+	aliases?: string[],
+};
+
+export type BookList = {
+	books: BookSummary[],
+	page: number,
+	total_pages: number,
 };
 
-export type CommandError = string;
+export type BookListKind = "lastUpdate" | "newest" | "popular" | "monthVisit" | "favorite" | "finished";
+
+export type BookSummary = {
+	id: string,
+	title: string,
+	author: string | null,
+	cover: string | null,
+	tags: string[],
+	status: string | null,
+};
+
+export type CacheConfig = {
+	enabled?: boolean,
+	dir?: string,
+	ttl?: number,
+	offline?: boolean,
+};
+
+export type CommandError = {
+	message: string,
+	siteError: SiteError | null,
+	remedy: string | null,
+};
 
 export type Config = {
 	output?: string,
 	template?: string,
 	cookie?: string,
+	cookieFile?: string,
 	userAgent?: string,
 	headers?: { [key in string]: string },
-	sleepTime?: number,
 	baseUrl?: string,
 	addCatalog?: boolean,
 	errorImg?: string[],
 	autoCheckUpdate?: boolean,
 	convertSimpleChinese?: boolean,
 	debug?: boolean,
+	cache?: CacheConfig,
+	recorder?: RecorderConfig,
+	retry?: RetryConfig,
+	rateLimit?: RateLimitConfig,
+	imageConcurrency?: number,
+	proxy?: ProxyConfig,
+	redirect?: RedirectConfig,
+	siteProfile?: string,
+	filter?: FilterConfig,
+	checkpointDir?: string,
+	queue?: QueueConfig,
+};
+
+export type DownloadEvent = { kind: "volumeStarted", bookId: string, volumeNo: number, title: string, chapters: number } | { kind: "chapterFetched", i: number, n: number, title: string } | { kind: "pageFetched", url: string } | { kind: "imageFetched", i: number, n: number } | { kind: "retrying", reason: string, attempt: number, retries: number, delay: number } | { kind: "volumeSaved", path: string } | { kind: "failed", volumeNo: number, error: string };
+
+export type FilterConfig = {
+	rules?: FilterRule[],
+	dryRun?: boolean,
+};
+
+export type FilterRule = {
+	pattern: string,
+	regex?: boolean,
+	scope?: FilterScope,
+};
+
+export type FilterScope = "paragraph" | "chapter";
+
+export type HostRateLimit = {
+	requestsPerMinute: number,
+	burst: number,
+};
+
+export type HttpMode = "live" | "record" | "replay";
+
+export type ProxyConfig = {
+	url?: string,
+	username?: string,
+	password?: string,
+	noProxy?: string[],
+	imageUrl?: string,
+};
+
+export type QueueConfig = {
+	file?: string,
+	retry?: RetryPolicy,
 };
 
+export type QueueEntry = {
+	id: number,
+	bookId: string,
+	title?: string | null,
+	volumes: string,
+	mode?: QueueMode,
+	status?: QueueStatus,
+	attempts?: number,
+	error?: string | null,
+};
+
+export type QueueMode = "download" | "update" | "omnibus";
+
+export type QueueStatus = "pending" | "running" | "done" | "failed";
+
+export type RateLimitConfig = {
+	page?: HostRateLimit,
+	image?: HostRateLimit,
+	hosts?: { [key in string]: HostRateLimit },
+};
+
+export type RecorderConfig = {
+	mode?: HttpMode,
+	dir?: string,
+};
+
+export type RedirectConfig = {
+	allowedHosts?: string[],
+	blockedPatterns?: string[],
+};
+
+export type RetryConfig = {
+	network?: RetryPolicy,
+	antiBot?: RetryPolicy,
+	unavailable?: RetryPolicy,
+	image?: RetryPolicy,
+};
+
+export type RetryPolicy = {
+	maxAttempts: number,
+	baseDelay: number,
+	maxDelay: number,
+	jitter?: number,
+};
+
+export type SiteError = { kind: "deleted" } | { kind: "notReviewed" } | { kind: "notFound" } | { kind: "blocked", message: string } | { kind: "redirected", from: string, to: string } | { kind: "network", message: string } | { kind: "parse", message: string } | { kind: "cacheMiss", url: string };
+
 export type Volume = {
 	id: string,
 	title: string | null,
@@ -60,6 +202,10 @@ export type Volume = {
 	volume_no: number,
 	updated_at: string,
 	path: string,
+	description?: string | null,
+	last_update?: string | null,
+	word_count?: number | null,
+	chapter_count?: number | null,
 };
 
 export type VolumeInfo = {
@@ -69,5 +215,9 @@ export type VolumeInfo = {
 	url_vol: string | null,
 	volume_no: number,
 	cover: string | null,
+	description?: string | null,
+	last_update?: string | null,
+	word_count?: number | null,
+	chapter_count?: number | null,
 };
 
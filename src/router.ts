@@ -2,6 +2,7 @@ import { createRouter, createWebHashHistory } from 'vue-router';
 
 import Config from './views/Config.vue';
 import Manage from './views/Manage.vue';
+import Queue from './views/Queue.vue';
 import Search from './views/Search.vue';
 
 const routes = [
@@ -17,6 +18,10 @@ const routes = [
     path: '/config',
     component: Config,
   },
+  {
+    path: '/queue',
+    component: Queue,
+  },
   {
     path: '/manage',
     component: Manage,
@@ -2,6 +2,7 @@ import { useRunCommand } from '@/composables/useRunCommand';
 
 import { commands } from '../bindings';
 import { useNotify } from '../composables/useNotification';
+import { describeError } from './commandError';
 
 let hasChecked = false;
 
@@ -50,7 +51,7 @@ export function checkUpdateNotify(
       }
     },
     onError: (err) => {
-      notify.error({ content: `检查更新失败：${err}` });
+      notify.error({ content: describeError(err, '检查更新失败', '检查更新失败，请检查网络连接！') });
     },
   });
 }
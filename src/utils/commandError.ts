@@ -0,0 +1,16 @@
+import type { CommandError } from '../bindings';
+
+function isCommandError(err: unknown): err is CommandError {
+  return typeof err === 'object' && err !== null && 'message' in err;
+}
+
+// 把命令返回的错误转换为提示文字，站点错误附带处理建议
+export function describeError(err: unknown, title: string, fallback: string): string {
+  if (!isCommandError(err)) {
+    return fallback;
+  }
+  if (err.remedy) {
+    return `${title}：${err.message}。${err.remedy}！`;
+  }
+  return `${title}：${err.message}`;
+}